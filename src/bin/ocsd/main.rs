@@ -0,0 +1,480 @@
+//! Command-line tool for inspecting and writing the OCSD buffer.
+
+mod view;
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use ocsd::{
+    client::{
+        backend::{DevMemBackend, FileBackend, MemoryBackend},
+        base_address,
+        discovery::{self, MemoryRange},
+        shutdown::stop_on_signals,
+        snapshot::OcsdSnapshot,
+        OcsdContext,
+    },
+    config::Config,
+    daemon::Daemon,
+    sysfs::{pci::PciAddress, SYSFS_ROOT},
+    DeviceVersion, OcsdDevice, OcsdDeviceFlags, OcsdDeviceHeader, OcsdHeader, OcsdSensor,
+    OcsdSensorLocation, OcsdSensorStatus, OcsdSensorType, TemperatureEncoding,
+};
+use serde::Serialize;
+use view::{DeviceView, DifferenceView, HeaderView, RawView};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Inspect and write the OCSD (Option Card Sensor Data) buffer"
+)]
+struct Cli {
+    /// Physical address of the OCSD header
+    #[arg(long, global = true, value_parser = parse_number, default_value_t = base_address::ML350_GEN9)]
+    base_address: usize,
+
+    /// Operate on a memory image file instead of /dev/mem
+    #[arg(long, global = true)]
+    image: Option<PathBuf>,
+
+    /// Physical address corresponding to the start of the memory image
+    /// [default: the base address]
+    #[arg(long, global = true, value_parser = parse_number)]
+    image_base: Option<usize>,
+
+    /// Encoding of temperatures in the OCSD buffer: unsigned, signed or
+    /// offset:<degrees> [default: that of the header's OCSD version]
+    #[arg(long, global = true)]
+    temperature_encoding: Option<TemperatureEncoding>,
+
+    /// Print output as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the OCSD header
+    Header,
+    /// List all device slots with their decoded sensors
+    Devices,
+    /// Print raw bytes of the header and device records
+    Hexdump {
+        /// Only print the record for this device slot
+        #[arg(long)]
+        slot: Option<u8>,
+    },
+    /// Set the number of device slots in use
+    SetBuffersInUse {
+        /// Number of slots to enable, starting from slot 0
+        count: u8,
+    },
+    /// Write a single sensor reading to a device slot
+    WriteSensor {
+        /// Device slot to write
+        #[arg(long)]
+        slot: u8,
+        /// Sensor index within the device
+        #[arg(long, default_value_t = 0)]
+        sensor: usize,
+        /// PCI bus of the device
+        #[arg(long, value_parser = parse_number, required_unless_present = "pci")]
+        bus: Option<usize>,
+        /// PCI device number of the device
+        #[arg(long, value_parser = parse_number, default_value = "0", conflicts_with = "pci")]
+        pci_device: usize,
+        /// PCI address (e.g. 0000:04:00.0) or sysfs path (e.g. /sys/class/hwmon/hwmon3)
+        /// of the device, instead of --bus and --pci-device
+        #[arg(long, conflicts_with = "bus")]
+        pci: Option<String>,
+        /// Temperature reading, in degrees Celsius
+        #[arg(long, allow_negative_numbers = true)]
+        reading: i16,
+        /// Caution threshold, in degrees Celsius
+        #[arg(long, default_value_t = 90)]
+        caution: i16,
+        /// Maximum continuous threshold, in degrees Celsius
+        #[arg(long, default_value_t = 80)]
+        max_continuous: i16,
+        /// Location of the sensor on the option card
+        #[arg(long, value_enum, default_value_t = Location::InternalToAsic)]
+        location: Location,
+    },
+    /// Search memory reserved next to system RAM (or the whole memory image) for
+    /// OCSD headers
+    Discover {
+        /// Alignment at which to search for headers
+        #[arg(long, value_parser = parse_number, default_value_t = discovery::DEFAULT_SCAN_STEP)]
+        step: usize,
+    },
+    /// Forward hwmon temperatures into the OCSD buffer as described by a
+    /// configuration file
+    Daemon {
+        /// TOML or YAML configuration file
+        #[arg(long)]
+        config: PathBuf,
+    },
+    /// Save, compare or restore snapshots of the whole OCSD buffer
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotAction {
+    /// Save the header and all device slots to an image file
+    Save {
+        /// Image file to write
+        file: PathBuf,
+    },
+    /// Write a saved image back to the OCSD buffer
+    Restore {
+        /// Image file to restore
+        file: PathBuf,
+    },
+    /// List the bytes which differ between a saved image and the OCSD buffer,
+    /// or another image
+    Diff {
+        /// Original image file
+        old: PathBuf,
+        /// Image file to compare with [default: the OCSD buffer]
+        new: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Location {
+    InternalToAsic,
+    OnboardOther,
+}
+
+fn parse_number(value: &str) -> Result<usize, String> {
+    match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|e| e.to_string())
+}
+
+impl Cli {
+    fn backend(&self) -> Result<Box<dyn MemoryBackend>, Box<dyn Error>> {
+        match &self.image {
+            Some(path) => {
+                let image_base = self.image_base.unwrap_or(self.base_address);
+                let backend = match self.command {
+                    Command::SetBuffersInUse { .. }
+                    | Command::WriteSensor { .. }
+                    | Command::Daemon { .. }
+                    | Command::Snapshot {
+                        action: SnapshotAction::Restore { .. },
+                    } => FileBackend::open(path, image_base)?,
+                    _ => FileBackend::open_read_only(path, image_base)?,
+                };
+                Ok(Box::new(backend))
+            }
+            None => Ok(Box::new(DevMemBackend)),
+        }
+    }
+
+    fn temperature_encoding(&self, header: &OcsdHeader) -> TemperatureEncoding {
+        self.temperature_encoding
+            .unwrap_or_else(|| TemperatureEncoding::for_version(header.ocsd_version))
+    }
+
+    fn output<T: Serialize>(&self, value: &T, print: impl Fn(&T)) -> Result<(), Box<dyn Error>> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(value)?);
+        } else {
+            print(value);
+        }
+        Ok(())
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(&cli) {
+        eprintln!("error: {}", e);
+        if cli.image.is_none() {
+            eprintln!("(accessing the OCSD buffer via /dev/mem requires root privileges)");
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let backend = cli.backend()?;
+    if let Command::Discover { step } = cli.command {
+        return discover(cli, backend.as_ref(), step);
+    }
+    if let Command::Snapshot {
+        action:
+            SnapshotAction::Diff {
+                ref old,
+                new: Some(ref new),
+            },
+    } = cli.command
+    {
+        return diff(cli, &OcsdSnapshot::load(old)?, &OcsdSnapshot::load(new)?);
+    }
+    if let Command::Snapshot {
+        action: SnapshotAction::Restore { ref file },
+    } = cli.command
+    {
+        return restore(cli, backend.as_ref(), file);
+    }
+
+    let mut context = OcsdContext::with_backend(backend.as_ref(), cli.base_address)?;
+    let header = context.read_header_checked()?;
+    let encoding = cli.temperature_encoding(&header);
+
+    match cli.command {
+        Command::Header => cli.output(&HeaderView::new(cli.base_address, &header), |h| h.print()),
+        Command::Devices => {
+            let mut devices = Vec::new();
+            for (slot, device_context) in context.device_mappings.iter_mut().enumerate() {
+                let slot = slot as u8;
+                let raw = device_context.read_raw()?;
+                devices.extend(DeviceView::new(
+                    slot,
+                    slot < header.buffers_in_use,
+                    &raw,
+                    encoding,
+                ));
+            }
+            cli.output(&devices, |devices| {
+                devices.iter().for_each(DeviceView::print)
+            })
+        }
+        Command::Hexdump { slot } => {
+            let mut records = Vec::new();
+            if slot.is_none() {
+                records.push(RawView {
+                    name: "header".to_string(),
+                    address: cli.base_address,
+                    bytes: context.read_header_raw()?,
+                });
+            }
+            for (i, device_context) in context.device_mappings.iter_mut().enumerate() {
+                if slot.is_some_and(|slot| slot as usize != i) {
+                    continue;
+                }
+                records.push(RawView {
+                    name: format!("slot {}", i),
+                    address: header.buffer_start_address as usize
+                        + i * header.one_option_card_size as usize,
+                    bytes: device_context.read_raw()?,
+                });
+            }
+            if records.is_empty() {
+                return Err(format!("no such slot: {}", slot.unwrap_or_default()).into());
+            }
+            cli.output(&records, |records| records.iter().for_each(RawView::print))
+        }
+        Command::SetBuffersInUse { count } => {
+            let header = context.set_buffers_in_use(count)?;
+            cli.output(&HeaderView::new(cli.base_address, &header), |h| h.print())
+        }
+        Command::WriteSensor {
+            slot,
+            sensor,
+            bus,
+            pci_device,
+            ref pci,
+            reading,
+            caution,
+            max_continuous,
+            location,
+        } => {
+            let (bus, pci_device) = match pci {
+                Some(pci) => {
+                    let header = PciAddress::find(SYSFS_ROOT, pci)?
+                        .device_header(OcsdDeviceFlags::HOST_REPORTED)?;
+                    (header.pci_bus, header.pci_device)
+                }
+                None => (
+                    bus.unwrap_or_default()
+                        .try_into()
+                        .map_err(|_| "PCI bus must fit in a byte")?,
+                    pci_device
+                        .try_into()
+                        .map_err(|_| "PCI device must fit in a byte")?,
+                ),
+            };
+            if sensor >= header.sensors_per_device() {
+                return Err(format!(
+                    "sensor index must be less than {}",
+                    header.sensors_per_device()
+                )
+                .into());
+            }
+            let device_context = context
+                .device_mappings
+                .get_mut(slot as usize)
+                .ok_or_else(|| format!("no such slot: {}", slot))?;
+
+            // keep other sensors and continue the update count when the slot
+            // already holds a valid record for the same device
+            let mut device = match device_context.read_checked() {
+                Ok(device)
+                    if device.header.pci_bus == bus
+                        && matches!(device.header.version, DeviceVersion::Version1) =>
+                {
+                    device
+                }
+                _ => OcsdDevice {
+                    header: OcsdDeviceHeader::host_reported(bus, pci_device),
+                    sensors: Vec::new(),
+                },
+            };
+            device
+                .sensors
+                .resize_with(header.sensors_per_device(), Default::default);
+            device.header.pci_device = pci_device;
+            let update_count = match device.sensors[sensor].bus {
+                Some(_) => device.sensors[sensor].update_count.wrapping_add(1),
+                None => 0,
+            };
+            device.sensors[sensor] = OcsdSensor {
+                sensor_type: OcsdSensorType::Thermal,
+                sensor_location: match location {
+                    Location::InternalToAsic => OcsdSensorLocation::InternalToAsic,
+                    Location::OnboardOther => OcsdSensorLocation::OnboardOther,
+                },
+                configuration: Default::default(),
+                status: OcsdSensorStatus::WithChecksum
+                    | OcsdSensorStatus::Present
+                    | OcsdSensorStatus::NotFailed,
+                max_continuous_threshold: encoding.encode(max_continuous)?,
+                caution_threshold: encoding.encode(caution)?,
+                reading: encoding.encode(reading)?,
+                update_count,
+                bus: Some(bus),
+            };
+            device_context.write_verified(&device)?;
+
+            if slot >= header.buffers_in_use {
+                eprintln!(
+                    "warning: slot {} is not in use; run `set-buffers-in-use {}` to enable it",
+                    slot,
+                    slot + 1
+                );
+            }
+            let raw = device_context.read_raw()?;
+            let view = DeviceView::new(slot, slot < header.buffers_in_use, &raw, encoding)
+                .ok_or("unable to read back device record")?;
+            cli.output(&view, DeviceView::print)
+        }
+        Command::Daemon { ref config } => {
+            let config = Config::load(config)?;
+            config.validate(&header)?;
+            let mut daemon_config = config.daemon_config()?;
+            if cli.temperature_encoding.is_some() {
+                daemon_config.temperature_encoding = cli.temperature_encoding;
+            }
+            // installed first, so that the shutdown policy is applied however
+            // soon the daemon is stopped
+            let stop = stop_on_signals()?;
+            let mut daemon = Daemon::new(context, daemon_config)?;
+            eprintln!(
+                "reporting {} slot(s) every {}s",
+                config.slots.len(),
+                daemon.update_interval().as_secs()
+            );
+            daemon.run(&stop)?;
+            eprintln!("stopping, shutdown policy {}", config.shutdown_policy);
+            Ok(daemon.shutdown()?)
+        }
+        Command::Snapshot { ref action } => {
+            let snapshot = OcsdSnapshot::capture(&mut context)?;
+            match action {
+                SnapshotAction::Save { file } => {
+                    snapshot.save(file)?;
+                    eprintln!("saved {} slot(s) to {}", snapshot.slots(), file.display());
+                    Ok(())
+                }
+                SnapshotAction::Diff { old, .. } => diff(cli, &OcsdSnapshot::load(old)?, &snapshot),
+                SnapshotAction::Restore { .. } => unreachable!(),
+            }
+        }
+        Command::Discover { .. } => unreachable!(),
+    }
+}
+
+fn diff(cli: &Cli, old: &OcsdSnapshot, new: &OcsdSnapshot) -> Result<(), Box<dyn Error>> {
+    let differences: Vec<DifferenceView> = old.diff(new).iter().map(DifferenceView::new).collect();
+    cli.output(&differences, |differences| {
+        if differences.is_empty() {
+            println!("no differences");
+        }
+        differences.iter().for_each(|d| println!("{}", d.text));
+    })
+}
+
+#[derive(Serialize)]
+struct CandidateView {
+    confidence: String,
+    header: HeaderView,
+}
+
+/// Restores a snapshot without requiring the buffer's header to be valid, as
+/// a corrupt header is a reason to restore one.
+fn restore(cli: &Cli, backend: &dyn MemoryBackend, file: &Path) -> Result<(), Box<dyn Error>> {
+    let saved = OcsdSnapshot::load(file)?;
+    let differences = OcsdContext::with_backend(backend, cli.base_address)
+        .and_then(|mut context| OcsdSnapshot::capture(&mut context))
+        .map(|current| current.diff(&saved).len());
+    saved.restore_with_backend(backend, cli.base_address)?;
+    match differences {
+        Ok(differences) => eprintln!(
+            "restored {} ({} difference(s) from the buffer)",
+            file.display(),
+            differences
+        ),
+        Err(e) => eprintln!(
+            "restored {} over an unreadable buffer: {}",
+            file.display(),
+            e
+        ),
+    }
+    Ok(())
+}
+
+fn discover(cli: &Cli, backend: &dyn MemoryBackend, step: usize) -> Result<(), Box<dyn Error>> {
+    let ranges = match &cli.image {
+        Some(path) => {
+            let start = cli.image_base.unwrap_or(cli.base_address);
+            let len = std::fs::metadata(path)?.len() as usize;
+            vec![MemoryRange {
+                start,
+                end: start + len.saturating_sub(1),
+                kind: "Image".to_string(),
+            }]
+        }
+        None => discovery::reserved_ranges("/"),
+    };
+    let candidates: Vec<CandidateView> = discovery::scan(backend, &ranges, step)
+        .iter()
+        .map(|candidate| CandidateView {
+            confidence: format!("{:?}", candidate.confidence).to_lowercase(),
+            header: HeaderView::new(candidate.base_address, &candidate.header),
+        })
+        .collect();
+    cli.output(&candidates, |candidates| {
+        if candidates.is_empty() {
+            println!("no OCSD header found");
+        }
+        for candidate in candidates {
+            println!("candidate ({} confidence):", candidate.confidence);
+            candidate.header.print();
+            println!();
+        }
+    })
+}
@@ -0,0 +1,207 @@
+//! Serialisable views of OCSD structures for CLI output.
+
+use ocsd::{
+    client::snapshot::SnapshotDifference, DeviceVersion, MemoryMapped, OcsdDevice, OcsdDeviceFlags,
+    OcsdDeviceHeader, OcsdHeader, OcsdSensor, OcsdSensorLocation, OcsdSensorState,
+    OcsdSensorStatus, OcsdSensorType, OcsdVersion, TemperatureEncoding,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct HeaderView {
+    pub base_address: usize,
+    pub ocsd_version: OcsdVersion,
+    pub buffer_size: u16,
+    pub max_option_cards: u8,
+    pub one_option_card_size: u8,
+    pub buffer_start_address: u32,
+    pub update_interval: u8,
+    pub buffers_in_use: u8,
+}
+
+impl HeaderView {
+    pub fn new(base_address: usize, header: &OcsdHeader) -> Self {
+        Self {
+            base_address,
+            ocsd_version: header.ocsd_version,
+            buffer_size: header.buffer_size,
+            max_option_cards: header.max_option_cards,
+            one_option_card_size: header.one_option_card_size,
+            buffer_start_address: header.buffer_start_address,
+            update_interval: header.update_interval,
+            buffers_in_use: header.buffers_in_use,
+        }
+    }
+
+    pub fn print(&self) {
+        println!("base address:         {:#x}", self.base_address);
+        println!("ocsd version:         {}", self.ocsd_version);
+        println!("buffer size:          {:#x}", self.buffer_size);
+        println!("max option cards:     {}", self.max_option_cards);
+        println!("option card size:     {:#x}", self.one_option_card_size);
+        println!("buffer start address: {:#x}", self.buffer_start_address);
+        println!("update interval:      {}", self.update_interval);
+        println!("buffers in use:       {}", self.buffers_in_use);
+    }
+}
+
+#[derive(Serialize)]
+pub struct SensorView {
+    pub index: usize,
+    pub sensor_type: OcsdSensorType,
+    pub sensor_location: OcsdSensorLocation,
+    pub configuration: u16,
+    #[serde(skip)]
+    pub configuration_text: String,
+    pub status: OcsdSensorStatus,
+    pub state: OcsdSensorState,
+    pub max_continuous_threshold: i16,
+    pub caution_threshold: i16,
+    pub reading: i16,
+    pub update_count: u16,
+}
+
+impl SensorView {
+    fn new(index: usize, sensor: &OcsdSensor, encoding: TemperatureEncoding) -> Self {
+        Self {
+            index,
+            sensor_type: sensor.sensor_type,
+            sensor_location: sensor.sensor_location,
+            configuration: sensor.configuration.bits(),
+            configuration_text: sensor.configuration.to_string(),
+            status: sensor.status,
+            state: sensor.state(),
+            max_continuous_threshold: encoding.decode(sensor.max_continuous_threshold),
+            caution_threshold: encoding.decode(sensor.caution_threshold),
+            reading: encoding.decode(sensor.reading),
+            update_count: sensor.update_count,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DeviceView {
+    pub slot: u8,
+    pub in_use: bool,
+    /// Set when the record fails validation; the remaining fields are then
+    /// decoded without validation.
+    pub error: Option<String>,
+    pub version: DeviceVersion,
+    pub pci_bus: u8,
+    pub pci_device: u8,
+    pub flags_caps: OcsdDeviceFlags,
+    /// Sensors which are not null
+    pub sensors: Vec<SensorView>,
+}
+
+impl DeviceView {
+    /// Decodes a raw device record, falling back to decoding without
+    /// validation if the record is invalid.
+    pub fn new(slot: u8, in_use: bool, raw: &[u8], encoding: TemperatureEncoding) -> Option<Self> {
+        let (device, error) = match OcsdDevice::try_from(raw) {
+            Ok(device) => (device, None),
+            Err(e) if raw.len() >= OcsdDeviceHeader::memory_size() => {
+                (OcsdDevice::from_bytes(raw), Some(e.to_string()))
+            }
+            Err(_) => return None,
+        };
+        let sensor_bytes = &raw[OcsdDeviceHeader::memory_size()..];
+        Some(Self {
+            slot,
+            in_use,
+            error,
+            version: device.header.version,
+            pci_bus: device.header.pci_bus,
+            pci_device: device.header.pci_device,
+            flags_caps: device.header.flags_caps,
+            sensors: device
+                .sensors
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| {
+                    sensor_bytes
+                        .chunks(OcsdSensor::memory_size())
+                        .nth(*index)
+                        .is_some_and(|bytes| bytes.iter().any(|b| *b != 0))
+                })
+                .map(|(index, sensor)| SensorView::new(index, sensor, encoding))
+                .collect(),
+        })
+    }
+
+    pub fn print(&self) {
+        println!(
+            "slot {}{}: {}, pci {:02x}:{:02x}, flags/caps {}",
+            self.slot,
+            if self.in_use { "" } else { " (not in use)" },
+            self.version,
+            self.pci_bus,
+            self.pci_device,
+            self.flags_caps
+        );
+        if let Some(error) = &self.error {
+            println!("  invalid record: {}", error);
+        }
+        for sensor in &self.sensors {
+            println!(
+                "  sensor {}: {} {}, {}, reading {}°C (caution {}°C, max continuous {}°C), \
+                 configuration {}, status {}, update count {}",
+                sensor.index,
+                sensor.sensor_type,
+                sensor.sensor_location,
+                sensor.state,
+                sensor.reading,
+                sensor.caution_threshold,
+                sensor.max_continuous_threshold,
+                sensor.configuration_text,
+                sensor.status,
+                sensor.update_count
+            );
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RawView {
+    pub name: String,
+    pub address: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl RawView {
+    pub fn print(&self) {
+        println!("{} @ {:#x}:", self.name, self.address);
+        for (i, line) in self.bytes.chunks(16).enumerate() {
+            print!("  {:04x}:", i * 16);
+            for (j, b) in line.iter().enumerate() {
+                if j == 8 {
+                    print!(" ");
+                }
+                print!(" {:02x}", b);
+            }
+            println!();
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DifferenceView {
+    pub region: String,
+    pub offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+    #[serde(skip)]
+    pub text: String,
+}
+
+impl DifferenceView {
+    pub fn new(difference: &SnapshotDifference) -> Self {
+        Self {
+            region: difference.region.to_string(),
+            offset: difference.offset,
+            old: difference.old.clone(),
+            new: difference.new.clone(),
+            text: difference.to_string(),
+        }
+    }
+}
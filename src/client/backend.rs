@@ -0,0 +1,364 @@
+//! Memory backends providing access to the physical memory containing the OCSD buffer.
+//!
+//! `DevMemBackend` (with the `devmem` feature) accesses the live buffer via `/dev/mem`,
+//! while [InMemoryBackend] and [FileBackend] simulate it for testing and offline analysis.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::unix::fs::FileExt,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use super::error::MappingError;
+
+/// A mapped region of physical memory, which can be read and written at
+/// byte offsets relative to the start of the region.
+pub trait MemoryRegion: Send {
+    /// Length of the region in bytes.
+    fn len(&self) -> usize;
+
+    /// Returns true if the region has a length of zero.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies `buf.len()` bytes starting at `offset` within the region into `buf`.
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<()>;
+
+    /// Copies `data` into the region starting at `offset`.
+    fn write(&mut self, offset: usize, data: &[u8]) -> io::Result<()>;
+}
+
+/// Provides [MemoryRegion]s for ranges of physical memory.
+pub trait MemoryBackend {
+    /// Maps `len` bytes of physical memory starting at `address`.
+    fn map(&self, address: usize, len: usize) -> Result<Box<dyn MemoryRegion>, MappingError>;
+}
+
+fn check_bounds(region_len: usize, offset: usize, len: usize) -> io::Result<()> {
+    match offset.checked_add(len) {
+        Some(end) if end <= region_len => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "access of {} bytes at offset {:#x} exceeds region length {:#x}",
+                len, offset, region_len
+            ),
+        )),
+    }
+}
+
+/// Backend accessing physical memory via `/dev/mem`.
+///
+/// Requires root privileges, and a kernel which permits access to the OCSD
+/// buffer's memory range.
+#[cfg(feature = "devmem")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DevMemBackend;
+
+/// A mapping of `/dev/mem`, which starts at the page containing `address`.
+#[cfg(feature = "devmem")]
+struct DevMemRegion {
+    map_base: *mut libc::c_void,
+    map_len: usize,
+    /// Offset of `address` within the mapping
+    page_offset: usize,
+    len: usize,
+}
+
+// The mapping is a plain pointer into a process-wide shared mapping of
+// physical memory, so it may be used from any thread.
+#[cfg(feature = "devmem")]
+unsafe impl Send for DevMemRegion {}
+
+#[cfg(feature = "devmem")]
+impl MemoryBackend for DevMemBackend {
+    fn map(&self, address: usize, len: usize) -> Result<Box<dyn MemoryRegion>, MappingError> {
+        use std::os::{fd::AsRawFd, unix::fs::OpenOptionsExt};
+
+        if len == 0 {
+            return Err(MappingError::new("mapping length must be greater than 0"));
+        }
+        let error = |e: io::Error| {
+            MappingError::new(format!("unable to map {:x} via /dev/mem: {}", address, e))
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_SYNC)
+            .open("/dev/mem")
+            .map_err(error)?;
+
+        // mmap() only maps whole pages
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let page_offset = address % page_size;
+        let map_len = len + page_offset;
+        let map_base = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                (address - page_offset) as libc::off_t,
+            )
+        };
+        // e.g. EPERM for ranges outside those permitted by CONFIG_STRICT_DEVMEM
+        if map_base == libc::MAP_FAILED {
+            return Err(error(io::Error::last_os_error()));
+        }
+        Ok(Box::new(DevMemRegion {
+            map_base,
+            map_len,
+            page_offset,
+            len,
+        }))
+    }
+}
+
+#[cfg(feature = "devmem")]
+impl MemoryRegion for DevMemRegion {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<()> {
+        check_bounds(self.len, offset, buf.len())?;
+        unsafe {
+            let src = (self.map_base as *const u8).add(self.page_offset + offset);
+            std::ptr::copy_nonoverlapping(src, buf.as_mut_ptr(), buf.len());
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> io::Result<()> {
+        check_bounds(self.len, offset, data.len())?;
+        unsafe {
+            let dst = (self.map_base as *mut u8).add(self.page_offset + offset);
+            std::ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "devmem")]
+impl Drop for DevMemRegion {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map_base, self.map_len) };
+    }
+}
+
+/// Backend simulating a range of physical memory with an in-memory buffer.
+///
+/// Clones of the backend share the same buffer, so the contents may be
+/// inspected or modified while an [OcsdContext](super::OcsdContext) is using it.
+///
+/// # Examples
+/// ```
+/// use ocsd::client::backend::{InMemoryBackend, MemoryBackend};
+///
+/// let backend = InMemoryBackend::new(0x1000, 0x100);
+/// let mut region = backend.map(0x1010, 0x10).unwrap();
+/// region.write(0, &[0xab]).unwrap();
+/// assert_eq!(backend.contents()[0x10], 0xab);
+/// ```
+#[derive(Debug, Clone)]
+pub struct InMemoryBackend {
+    base_address: usize,
+    memory: Arc<Mutex<Vec<u8>>>,
+}
+
+impl InMemoryBackend {
+    /// Constructs a zeroed memory range of `len` bytes starting at `base_address`.
+    pub fn new(base_address: usize, len: usize) -> Self {
+        Self::from_bytes(base_address, vec![0x00; len])
+    }
+
+    /// Constructs a memory range starting at `base_address` with the provided contents.
+    pub fn from_bytes(base_address: usize, bytes: Vec<u8>) -> Self {
+        Self {
+            base_address,
+            memory: Arc::new(Mutex::new(bytes)),
+        }
+    }
+
+    /// Returns a copy of the current contents of the memory range.
+    pub fn contents(&self) -> Vec<u8> {
+        self.memory.lock().unwrap().clone()
+    }
+}
+
+struct InMemoryRegion {
+    memory: Arc<Mutex<Vec<u8>>>,
+    start: usize,
+    len: usize,
+}
+
+impl MemoryBackend for InMemoryBackend {
+    fn map(&self, address: usize, len: usize) -> Result<Box<dyn MemoryRegion>, MappingError> {
+        let memory_len = self.memory.lock().unwrap().len();
+        let start = address
+            .checked_sub(self.base_address)
+            .filter(|start| check_bounds(memory_len, *start, len).is_ok())
+            .ok_or_else(|| {
+                MappingError::new(format!(
+                    "range {:x}+{:x} is outside of simulated memory {:x}+{:x}",
+                    address, len, self.base_address, memory_len
+                ))
+            })?;
+        Ok(Box::new(InMemoryRegion {
+            memory: self.memory.clone(),
+            start,
+            len,
+        }))
+    }
+}
+
+impl MemoryRegion for InMemoryRegion {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<()> {
+        check_bounds(self.len, offset, buf.len())?;
+        let start = self.start + offset;
+        buf.copy_from_slice(&self.memory.lock().unwrap()[start..start + buf.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> io::Result<()> {
+        check_bounds(self.len, offset, data.len())?;
+        let start = self.start + offset;
+        self.memory.lock().unwrap()[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Backend simulating a range of physical memory with a file, such as a
+/// memory image dumped from a running system.
+///
+/// Byte 0 of the file corresponds to physical address `base_address`.
+#[derive(Debug)]
+pub struct FileBackend {
+    file: File,
+    base_address: usize,
+}
+
+impl FileBackend {
+    /// Opens a memory image file for reading and writing.
+    pub fn open(path: impl AsRef<Path>, base_address: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(Self { file, base_address })
+    }
+
+    /// Opens a memory image file for reading only. Writes to regions mapped
+    /// from this backend will fail.
+    pub fn open_read_only(path: impl AsRef<Path>, base_address: usize) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self { file, base_address })
+    }
+}
+
+struct FileRegion {
+    file: File,
+    start: u64,
+    len: usize,
+}
+
+impl MemoryBackend for FileBackend {
+    fn map(&self, address: usize, len: usize) -> Result<Box<dyn MemoryRegion>, MappingError> {
+        let file_len = self
+            .file
+            .metadata()
+            .map_err(|e| MappingError::new(format!("unable to read image metadata: {}", e)))?
+            .len();
+        let start = address
+            .checked_sub(self.base_address)
+            .filter(|start| check_bounds(file_len as usize, *start, len).is_ok())
+            .ok_or_else(|| {
+                MappingError::new(format!(
+                    "range {:x}+{:x} is outside of memory image {:x}+{:x}",
+                    address, len, self.base_address, file_len
+                ))
+            })?;
+        let file = self
+            .file
+            .try_clone()
+            .map_err(|e| MappingError::new(format!("unable to open image: {}", e)))?;
+        Ok(Box::new(FileRegion {
+            file,
+            start: start as u64,
+            len,
+        }))
+    }
+}
+
+impl MemoryRegion for FileRegion {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<()> {
+        check_bounds(self.len, offset, buf.len())?;
+        self.file.read_exact_at(buf, self.start + offset as u64)
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> io::Result<()> {
+        check_bounds(self.len, offset, data.len())?;
+        self.file.write_all_at(data, self.start + offset as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[cfg(feature = "devmem")]
+    #[test]
+    fn devmem_map_failure() {
+        // fails to open /dev/mem without privileges, or to map an offset
+        // which doesn't fit off_t, rather than returning an unusable mapping
+        assert!(DevMemBackend.map(usize::MAX - 0xfff, 0x10).is_err());
+        assert!(DevMemBackend.map(0x1000, 0).is_err());
+    }
+
+    #[test]
+    fn in_memory_bounds() {
+        let backend = InMemoryBackend::new(0x1000, 0x100);
+        assert!(backend.map(0x0fff, 0x10).is_err());
+        assert!(backend.map(0x10f0, 0x11).is_err());
+
+        let mut region = backend.map(0x10f0, 0x10).unwrap();
+        let mut buf = [0x00; 2];
+        assert!(region.read(0x0f, &mut buf).is_err());
+        assert!(region.write(0x0e, &[0x01, 0x02]).is_ok());
+        assert_eq!(backend.contents()[0xfe..], [0x01, 0x02]);
+    }
+
+    #[test]
+    fn file_backed() {
+        let mut image = tempfile::NamedTempFile::new().unwrap();
+        image.write_all(&[0x00; 0x40]).unwrap();
+
+        let backend = FileBackend::open(image.path(), 0x2000).unwrap();
+        let mut region = backend.map(0x2020, 0x20).unwrap();
+        region.write(0x04, &[0xaa, 0xbb]).unwrap();
+        assert!(backend.map(0x2030, 0x20).is_err());
+
+        let mut other = backend.map(0x2000, 0x40).unwrap();
+        let mut buf = [0x00; 3];
+        other.read(0x23, &mut buf).unwrap();
+        assert_eq!(buf, [0x00, 0xaa, 0xbb]);
+
+        let read_only = FileBackend::open_read_only(image.path(), 0x2000).unwrap();
+        assert!(read_only
+            .map(0x2000, 0x40)
+            .unwrap()
+            .write(0, &[0x01])
+            .is_err());
+    }
+}
@@ -1,4 +1,6 @@
 //! Base addresses for tested servers
+//!
+//! For other servers, see [discovery](super::discovery).
 
 /// HPE ProLiant ML350 Gen9
 pub const ML350_GEN9: usize = 0x791f6000;
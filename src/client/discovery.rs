@@ -0,0 +1,343 @@
+//! Discovery of the OCSD base address on servers without a known [base_address](super::base_address).
+//!
+//! The OCSD header is located in memory reserved by the system firmware. Candidate
+//! regions are read from `/proc/iomem` or the firmware memory map in
+//! `/sys/firmware/memmap`, then scanned for a header whose checksum validates.
+//! Only reserved ranges adjoining system RAM are scanned, as on the ML350 Gen9,
+//! so that device memory in reserved ranges of the PCI hole is never read.
+
+use std::{cmp::Reverse, fs, io, path::Path};
+
+use super::backend::MemoryBackend;
+use crate::protocol::{MemoryMapped, OcsdDeviceHeader, OcsdHeader, OcsdSensor};
+
+/// Default alignment at which candidate headers are searched for.
+pub const DEFAULT_SCAN_STEP: usize = 0x1000;
+
+const SCAN_CHUNK_SIZE: usize = 0x10_0000;
+
+/// A range of physical memory, as described by the kernel or system firmware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRange {
+    /// First address in the range
+    pub start: usize,
+    /// Last address in the range (inclusive)
+    pub end: usize,
+    /// Type of the range, e.g. `Reserved` or `System RAM`
+    pub kind: String,
+}
+
+impl MemoryRange {
+    /// Size of the range in bytes.
+    pub fn size(&self) -> usize {
+        self.end - self.start + 1
+    }
+
+    /// Returns true if the range is marked as reserved by the system firmware.
+    pub fn is_reserved(&self) -> bool {
+        self.kind.eq_ignore_ascii_case("reserved")
+    }
+
+    /// Returns true if the range is usable RAM.
+    pub fn is_system_ram(&self) -> bool {
+        self.kind.eq_ignore_ascii_case("system ram")
+    }
+
+    /// Returns true if the range starts right after `other` ends, or the
+    /// other way around.
+    fn adjoins(&self, other: &MemoryRange) -> bool {
+        self.end.checked_add(1) == Some(other.start) || other.end.checked_add(1) == Some(self.start)
+    }
+
+    fn contains(&self, address: usize, len: usize) -> bool {
+        address >= self.start
+            && address
+                .checked_add(len)
+                .is_some_and(|end| end - 1 <= self.end)
+    }
+}
+
+/// Parses the contents of `/proc/iomem` into a list of memory ranges,
+/// including nested ranges.
+///
+/// Note that the kernel reports all addresses as zero unless `/proc/iomem`
+/// is read with root privileges.
+pub fn parse_iomem(contents: &str) -> Vec<MemoryRange> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (range, kind) = line.trim().split_once(" : ")?;
+            let (start, end) = range.split_once('-')?;
+            Some(MemoryRange {
+                start: usize::from_str_radix(start, 16).ok()?,
+                end: usize::from_str_radix(end, 16).ok()?,
+                kind: kind.trim().to_string(),
+            })
+        })
+        .filter(|range| range.end > range.start)
+        .collect()
+}
+
+/// Reads the firmware-provided (e820 or EFI) memory map from a
+/// `/sys/firmware/memmap` directory.
+pub fn read_firmware_memmap(memmap_dir: impl AsRef<Path>) -> io::Result<Vec<MemoryRange>> {
+    let parse_address = |path: &Path| -> io::Result<usize> {
+        let value = fs::read_to_string(path)?;
+        usize::from_str_radix(value.trim().trim_start_matches("0x"), 16)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    };
+
+    let mut ranges = Vec::new();
+    for entry in fs::read_dir(memmap_dir)? {
+        let path = entry?.path();
+        ranges.push(MemoryRange {
+            start: parse_address(&path.join("start"))?,
+            end: parse_address(&path.join("end"))?,
+            kind: fs::read_to_string(path.join("type"))?.trim().to_string(),
+        });
+    }
+    ranges.sort_by_key(|range| range.start);
+    Ok(ranges)
+}
+
+/// Returns reserved memory ranges from the firmware memory map and `/proc/iomem`
+/// below `root`, which is `/` on a live system.
+///
+/// Only ranges which directly precede or follow a system RAM range are
+/// returned: memory reserved by the firmware out of RAM, such as the OCSD
+/// buffer, adjoins it, while reserved device memory lies in the PCI hole.
+/// Sources which can't be read are skipped, and duplicate ranges are removed.
+pub fn reserved_ranges(root: impl AsRef<Path>) -> Vec<MemoryRange> {
+    let root = root.as_ref();
+    let mut ranges = read_firmware_memmap(root.join("sys/firmware/memmap")).unwrap_or_default();
+    if let Ok(iomem) = fs::read_to_string(root.join("proc/iomem")) {
+        ranges.extend(parse_iomem(&iomem));
+    }
+    let ram: Vec<MemoryRange> = ranges
+        .iter()
+        .filter(|range| range.is_system_ram())
+        .cloned()
+        .collect();
+    ranges.retain(|range| range.is_reserved() && ram.iter().any(|ram| ram.adjoins(range)));
+    ranges.sort_by_key(|range| (range.start, range.end));
+    ranges.dedup_by_key(|range| (range.start, range.end));
+    ranges
+}
+
+/// Level of confidence that a candidate is really the OCSD header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Header is valid, but its layout is inconsistent with its memory range
+    Low,
+    /// Header is valid, and its layout is partially consistent with its memory range
+    Medium,
+    /// Header is valid, and its devices buffer lies within the same reserved range
+    High,
+}
+
+/// A plausible OCSD header found in memory.
+pub struct Candidate {
+    /// Physical address of the header, which can be passed to
+    /// [OcsdContext::with_backend](super::OcsdContext::with_backend)
+    pub base_address: usize,
+    /// The decoded header
+    pub header: OcsdHeader,
+    /// Confidence that this is the OCSD header
+    pub confidence: Confidence,
+}
+
+fn evaluate(base_address: usize, bytes: &[u8], range: &MemoryRange) -> Option<Candidate> {
+    let header = OcsdHeader::try_from(bytes).ok()?;
+    let min_device_size = OcsdDeviceHeader::memory_size() + OcsdSensor::memory_size();
+    if header.max_option_cards == 0
+        || (header.one_option_card_size as usize) < min_device_size
+        || header.buffers_in_use > header.max_option_cards
+    {
+        return None;
+    }
+
+    let devices_size = header.max_option_cards as usize * header.one_option_card_size as usize;
+    let buffer_start = header.buffer_start_address as usize;
+    let size_consistent = header.buffer_size as usize == devices_size;
+    let buffer_in_range = range.contains(buffer_start, devices_size)
+        && (buffer_start >= base_address + OcsdHeader::memory_size()
+            || buffer_start + devices_size <= base_address);
+
+    let confidence = match (size_consistent, buffer_in_range) {
+        (true, true) => Confidence::High,
+        (false, false) => Confidence::Low,
+        _ => Confidence::Medium,
+    };
+    Some(Candidate {
+        base_address,
+        header,
+        confidence,
+    })
+}
+
+/// Scans memory ranges for plausible OCSD headers, at multiples of `step` bytes
+/// from the start of each range.
+///
+/// Candidates are returned with the most confident first. Chunks of ranges
+/// which the backend fails to map or read are skipped; note that reading
+/// device memory which can be mapped may have side effects, so ranges should
+/// be limited to RAM, as by [reserved_ranges].
+pub fn scan(backend: &dyn MemoryBackend, ranges: &[MemoryRange], step: usize) -> Vec<Candidate> {
+    let header_size = OcsdHeader::memory_size();
+    let step = step.max(1);
+    let mut candidates = Vec::new();
+
+    for range in ranges {
+        let mut chunk_start = range.start;
+        while chunk_start <= range.end && range.end - chunk_start + 1 >= header_size {
+            // overlap chunks so that headers spanning a chunk boundary are found
+            let chunk_len = (range.end - chunk_start + 1).min(SCAN_CHUNK_SIZE + header_size);
+            let mut chunk = vec![0x00; chunk_len];
+            let read = backend
+                .map(chunk_start, chunk_len)
+                .ok()
+                .and_then(|mut region| region.read(0, &mut chunk).ok());
+            if read.is_some() {
+                let mut offset = 0;
+                while offset + header_size <= chunk_len && offset < SCAN_CHUNK_SIZE {
+                    let address = chunk_start + offset;
+                    if let Some(candidate) =
+                        evaluate(address, &chunk[offset..offset + header_size], range)
+                    {
+                        candidates.push(candidate);
+                    }
+                    offset += step;
+                }
+            }
+            // keep scan positions aligned to the step across chunks
+            chunk_start += SCAN_CHUNK_SIZE.div_ceil(step) * step;
+        }
+    }
+
+    candidates.sort_by_key(|candidate| Reverse(candidate.confidence));
+    candidates
+}
+
+/// Discovers candidate OCSD base addresses on the running system, by scanning
+/// the reserved memory ranges adjoining system RAM via `/dev/mem`; see
+/// [reserved_ranges].
+///
+/// Requires root privileges.
+#[cfg(feature = "devmem")]
+pub fn discover() -> Vec<Candidate> {
+    scan(
+        &super::backend::DevMemBackend,
+        &reserved_ranges("/"),
+        DEFAULT_SCAN_STEP,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::{client::backend::FileBackend, protocol::OcsdVersion};
+
+    const IOMEM: &str = "\
+00000000-00000fff : Reserved
+00001000-0009ffff : System RAM
+000a0000-000bffff : PCI Bus 0000:00
+00100000-791f5fff : System RAM
+  01000000-01e0312e : Kernel code
+791f6000-791fffff : Reserved
+  791f6000-791f6fff : ACPI Tables
+fec00000-fec003ff : IOAPIC 0
+";
+
+    #[test]
+    fn iomem() {
+        let ranges = parse_iomem(IOMEM);
+        assert_eq!(ranges.len(), 8);
+        assert_eq!(
+            ranges[5],
+            MemoryRange {
+                start: 0x791f6000,
+                end: 0x791fffff,
+                kind: "Reserved".to_string(),
+            }
+        );
+        assert_eq!(ranges[4].kind, "Kernel code");
+        assert_eq!(ranges.iter().filter(|range| range.is_reserved()).count(), 2);
+    }
+
+    #[test]
+    fn system_ranges() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("proc")).unwrap();
+        // reserved device memory in the PCI hole is not scanned
+        let iomem = format!(
+            "{}{}",
+            IOMEM, "e0000000-efffffff : Reserved\n  e0000000-efffffff : PCI MMCONFIG 0000\n"
+        );
+        fs::write(root.path().join("proc/iomem"), iomem).unwrap();
+        let entry = root.path().join("sys/firmware/memmap/3");
+        fs::create_dir_all(&entry).unwrap();
+        fs::write(entry.join("start"), "0x791f6000\n").unwrap();
+        fs::write(entry.join("end"), "0x791fffff\n").unwrap();
+        fs::write(entry.join("type"), "Reserved\n").unwrap();
+
+        let ranges = reserved_ranges(root.path());
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[1].start, 0x791f6000);
+    }
+
+    fn header(buffer_start_address: u32, buffer_size: u16) -> OcsdHeader {
+        OcsdHeader {
+            ocsd_version: OcsdVersion::Version2,
+            buffer_size,
+            max_option_cards: 8,
+            one_option_card_size: 0xa0,
+            buffer_start_address,
+            update_interval: 1,
+            buffers_in_use: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scan_image() {
+        const IMAGE_BASE: usize = 0x79000000;
+        let mut image = vec![0xa5; 0x300000];
+        let ocsd = 0x1f6000;
+        image[ocsd..ocsd + 0x40]
+            .copy_from_slice(&header((IMAGE_BASE + ocsd + 0x40) as u32, 8 * 0xa0).to_bytes());
+        // decoy with a valid checksum, pointing outside of memory
+        let decoy = 0x1f8000;
+        image[decoy..decoy + 0x40].copy_from_slice(&header(0x1000, 0x1234).to_bytes());
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&image).unwrap();
+        let backend = FileBackend::open_read_only(file.path(), IMAGE_BASE).unwrap();
+
+        let ranges = [MemoryRange {
+            start: IMAGE_BASE + 0x100000,
+            end: IMAGE_BASE + 0x2fffff,
+            kind: "Reserved".to_string(),
+        }];
+        let candidates = scan(&backend, &ranges, DEFAULT_SCAN_STEP);
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].base_address, 0x791f6000);
+        assert_eq!(candidates[0].confidence, Confidence::High);
+        assert_eq!(candidates[0].header.max_option_cards, 8);
+        assert_eq!(candidates[1].base_address, 0x791f8000);
+        assert_eq!(candidates[1].confidence, Confidence::Low);
+    }
+
+    #[test]
+    fn scan_unmapped() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let backend = FileBackend::open_read_only(file.path(), 0).unwrap();
+        let ranges = [MemoryRange {
+            start: 0x1000,
+            end: 0x1fff,
+            kind: "Reserved".to_string(),
+        }];
+        assert!(scan(&backend, &ranges, DEFAULT_SCAN_STEP).is_empty());
+    }
+}
@@ -1,5 +1,10 @@
-use std::{error::Error, fmt::Display};
+//! OCSD client error types.
 
+use std::{error::Error, fmt::Display, io};
+
+use crate::protocol::error::DecodeError;
+
+/// Error used when a region of physical memory cannot be mapped.
 #[derive(Debug, Clone)]
 pub struct MappingError {
     msg: String,
@@ -18,3 +23,167 @@ impl Display for MappingError {
 }
 
 impl Error for MappingError {}
+
+/// A field of a device record whose value read back differs from the value
+/// written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMismatch {
+    /// Name of the field, e.g. `sensor 0 reading`
+    pub field: String,
+    /// Value written
+    pub written: i64,
+    /// Value read back
+    pub read: i64,
+}
+
+impl Display for FieldMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: wrote {}, read {}",
+            self.field, self.written, self.read
+        )
+    }
+}
+
+/// Error used when a device record read back after writing doesn't match
+/// the record written, e.g. because iLO or another process wrote the slot
+/// at the same time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The record read back failed validation
+    Invalid(DecodeError),
+    /// The record read back is valid, but these fields differ
+    Mismatch(Vec<FieldMismatch>),
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(e) => write!(f, "record read back is invalid: {}", e),
+            Self::Mismatch(fields) => {
+                write!(f, "record read back differs from record written (")?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    field.fmt(f)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl Error for VerifyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Invalid(e) => Some(e),
+            Self::Mismatch(_) => None,
+        }
+    }
+}
+
+/// Error returned by operations on the OCSD buffer.
+#[derive(Debug)]
+pub enum ClientError {
+    /// A region of the OCSD buffer could not be mapped
+    Mapping(MappingError),
+    /// A mapped region could not be read or written
+    Io(io::Error),
+    /// Data read from the OCSD buffer could not be decoded
+    Decode(DecodeError),
+    /// Reporter state could not be read or persisted
+    State(io::Error),
+    /// A device has more sensors than fit in its slot
+    TooManySensors {
+        /// Number of sensors which fit in the slot
+        capacity: usize,
+        /// Number of sensors in the device
+        sensors: usize,
+    },
+    /// A snapshot cannot be restored, as its layout differs from that of the
+    /// OCSD buffer
+    LayoutMismatch(String),
+    /// The requested number of buffers in use exceeds the number of device slots
+    TooManyBuffers {
+        /// Number of device slots
+        max_option_cards: u8,
+        /// Requested number of buffers in use
+        buffers_in_use: u8,
+    },
+    /// The header changed while being updated, e.g. because it was rewritten
+    /// by firmware
+    HeaderChanged,
+    /// A device record read back after writing doesn't match the record written
+    Verify(VerifyError),
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mapping(e) => e.fmt(f),
+            Self::Io(e) => write!(f, "unable to access ocsd buffer: {}", e),
+            Self::Decode(e) => write!(f, "unable to decode ocsd buffer: {}", e),
+            Self::State(e) => write!(f, "unable to access reporter state: {}", e),
+            Self::TooManySensors { capacity, sensors } => write!(
+                f,
+                "device has {} sensors, but its slot only fits {}",
+                sensors, capacity
+            ),
+            Self::LayoutMismatch(msg) => {
+                write!(f, "snapshot doesn't match ocsd buffer layout: {}", msg)
+            }
+            Self::TooManyBuffers {
+                max_option_cards,
+                buffers_in_use,
+            } => write!(
+                f,
+                "buffers in use ({}) must not exceed max option cards ({})",
+                buffers_in_use, max_option_cards
+            ),
+            Self::HeaderChanged => write!(f, "ocsd header changed while being updated"),
+            Self::Verify(e) => write!(f, "write verification failed: {}", e),
+        }
+    }
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Mapping(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Decode(e) => Some(e),
+            Self::State(e) => Some(e),
+            Self::Verify(e) => Some(e),
+            Self::TooManySensors { .. }
+            | Self::LayoutMismatch(_)
+            | Self::TooManyBuffers { .. }
+            | Self::HeaderChanged => None,
+        }
+    }
+}
+
+impl From<MappingError> for ClientError {
+    fn from(value: MappingError) -> Self {
+        Self::Mapping(value)
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<VerifyError> for ClientError {
+    fn from(value: VerifyError) -> Self {
+        Self::Verify(value)
+    }
+}
+
+impl From<DecodeError> for ClientError {
+    fn from(value: DecodeError) -> Self {
+        Self::Decode(value)
+    }
+}
@@ -1,18 +1,30 @@
-//! Client interface for interacting with the OCSD buffer via /dev/mem on Linux.
+//! Client interface for interacting with the OCSD buffer.
+//!
+//! The buffer is accessed through a [MemoryBackend].
+//! On Linux, the live buffer is available via `/dev/mem` with the `devmem` feature;
+//! simulated buffers can be used for testing.
 
+pub mod backend;
 pub mod base_address;
-mod error;
+pub mod discovery;
+pub mod error;
+pub mod reporter;
+pub mod shutdown;
+pub mod snapshot;
+pub mod watchdog;
 
-use devmem::Mapping;
-use error::MappingError;
+use backend::{MemoryBackend, MemoryRegion};
+use error::{ClientError, FieldMismatch, MappingError, VerifyError};
 
-use crate::protocol::{MemoryMapped, OcsdDevice, OcsdHeader};
+use crate::protocol::{MemoryMapped, OcsdDevice, OcsdDeviceHeader, OcsdHeader};
 
 const OCSD_HEADER_SIZE: usize = 0x40;
 
 /// Context representing the complete OCSD buffer, including header and all devices
 pub struct OcsdContext {
-    header_mapping: Mapping,
+    header_mapping: Box<dyn MemoryRegion>,
+    /// Header read when the context was opened, describing the device mappings
+    layout: OcsdHeader,
     /// Vec of device contexts, each corresponding to a slice of the OCSD buffer.
     /// All are open and available following construction of the [OcsdContext].
     pub device_mappings: Vec<OcsdDeviceContext>,
@@ -20,91 +32,634 @@ pub struct OcsdContext {
 
 /// Context representing a single OCSD device
 pub struct OcsdDeviceContext {
-    mapping: Mapping,
+    mapping: Box<dyn MemoryRegion>,
     device_size: u8,
 }
 
 impl OcsdHeader {
-    fn open_device_mapping(&self, device_index: u8) -> Result<Mapping, MappingError> {
+    fn open_device_mapping(
+        &self,
+        backend: &dyn MemoryBackend,
+        device_index: u8,
+    ) -> Result<Box<dyn MemoryRegion>, MappingError> {
         if device_index >= self.max_option_cards {
             return Err(MappingError::new(format!(
                 "requested device index {} doesn't fit max number of option cards {}",
                 device_index, self.max_option_cards
             )));
         }
+        if (self.one_option_card_size as usize) < OcsdDeviceHeader::memory_size() {
+            return Err(MappingError::new(format!(
+                "option card size {:#x} is too small for a device header",
+                self.one_option_card_size
+            )));
+        }
         let start_address = self.buffer_start_address as usize
             + (self.one_option_card_size as usize * device_index as usize);
-        unsafe {
-            Mapping::new(start_address, self.one_option_card_size as usize).map_err(|_| {
+        backend
+            .map(start_address, self.one_option_card_size as usize)
+            .map_err(|e| {
                 MappingError::new(format!(
-                    "unable to open device mapping at {:x}",
-                    start_address
+                    "unable to open device mapping at {:x}: {}",
+                    start_address, e
                 ))
             })
-        }
     }
 }
 
 impl OcsdContext {
-    /// Create a new [OcsdContext] given a provided base address.
+    /// Create a new [OcsdContext] given a provided base address, accessing
+    /// the OCSD buffer via `/dev/mem`.
     /// The header will be read and parsed to determine the number of available
     /// option card slots.
-    pub fn new(base_address: usize) -> Result<Self, MappingError> {
-        let header_mapping_result = unsafe { Mapping::new(base_address, OCSD_HEADER_SIZE) };
-        match header_mapping_result {
-            Ok(mut header_mapping) => {
-                let init_header = Self::_read_header(&mut header_mapping);
-                let mut device_mappings: Vec<OcsdDeviceContext> = Vec::new();
-
-                for i in 0..init_header.max_option_cards {
-                    match init_header.open_device_mapping(i) {
-                        Ok(device_mapping) => device_mappings.push(OcsdDeviceContext {
-                            mapping: device_mapping,
-                            device_size: init_header.one_option_card_size,
-                        }),
-                        Err(e) => return Err(e),
-                    }
-                }
-
-                Ok(Self {
-                    header_mapping,
-                    device_mappings,
-                })
-            }
-            Err(_) => Err(MappingError::new(format!(
-                "unable to open ocsd header at {:x}",
-                base_address
-            ))),
-        }
-    }
-
-    fn _read_header(header_mapping: &mut Mapping) -> OcsdHeader {
+    ///
+    /// Returns an error if no valid OCSD header is found at the base address.
+    #[cfg(feature = "devmem")]
+    pub fn new(base_address: usize) -> Result<Self, ClientError> {
+        Self::with_backend(&backend::DevMemBackend, base_address)
+    }
+
+    /// Create a new [OcsdContext] given a provided base address, accessing
+    /// the OCSD buffer through the provided backend.
+    /// The header will be read and parsed to determine the number of available
+    /// option card slots.
+    ///
+    /// Returns an error if no valid OCSD header is found at the base address.
+    pub fn with_backend(
+        backend: &dyn MemoryBackend,
+        base_address: usize,
+    ) -> Result<Self, ClientError> {
+        let mut header_mapping = backend.map(base_address, OCSD_HEADER_SIZE).map_err(|e| {
+            MappingError::new(format!(
+                "unable to open ocsd header at {:x}: {}",
+                base_address, e
+            ))
+        })?;
+        let init_header =
+            OcsdHeader::try_from(&Self::_read_header_bytes(&mut header_mapping)?[..])?;
+
+        let mut device_mappings: Vec<OcsdDeviceContext> = Vec::new();
+        for i in 0..init_header.max_option_cards {
+            device_mappings.push(OcsdDeviceContext {
+                mapping: init_header.open_device_mapping(backend, i)?,
+                device_size: init_header.one_option_card_size,
+            });
+        }
+
+        Ok(Self {
+            header_mapping,
+            layout: init_header,
+            device_mappings,
+        })
+    }
+
+    fn _read_header_bytes(
+        header_mapping: &mut Box<dyn MemoryRegion>,
+    ) -> Result<Vec<u8>, ClientError> {
         let mut header_data: Vec<u8> = vec![0x00; OCSD_HEADER_SIZE];
-        header_mapping.copy_into_slice(&mut header_data);
-        OcsdHeader::from_bytes(&header_data)
+        header_mapping.read(0, &mut header_data)?;
+        Ok(header_data)
+    }
+
+    /// Read the raw bytes of the header from the OCSD buffer.
+    pub fn read_header_raw(&mut self) -> Result<Vec<u8>, ClientError> {
+        Self::_read_header_bytes(&mut self.header_mapping)
     }
 
     /// Re-read and parse the header from the OCSD buffer.
-    pub fn read_header(&mut self) -> OcsdHeader {
-        Self::_read_header(&mut self.header_mapping)
+    pub fn read_header(&mut self) -> Result<OcsdHeader, ClientError> {
+        Ok(OcsdHeader::from_bytes(&Self::_read_header_bytes(
+            &mut self.header_mapping,
+        )?))
+    }
+
+    /// Re-read and parse the header from the OCSD buffer, validating its checksum.
+    pub fn read_header_checked(&mut self) -> Result<OcsdHeader, ClientError> {
+        Ok(OcsdHeader::from_bytes_checked(&Self::_read_header_bytes(
+            &mut self.header_mapping,
+        )?)?)
     }
 
     /// Replace the header in the OCSD buffer with the one provided.
-    pub fn write_header(&mut self, device: &OcsdHeader) {
-        self.header_mapping.copy_from_slice(&device.to_bytes());
+    ///
+    /// This rewrites fields owned by firmware, such as the buffer start address;
+    /// use [set_buffers_in_use](OcsdContext::set_buffers_in_use) to enable slots.
+    pub fn write_header(&mut self, device: &OcsdHeader) -> Result<(), ClientError> {
+        Ok(self.header_mapping.write(0, &device.to_bytes())?)
+    }
+
+    /// Set the number of device slots in use, starting from slot 0, returning
+    /// the updated header.
+    ///
+    /// Only the buffers in use field and the checksum are written. The header
+    /// must be valid and describe the same slots as when the context was opened,
+    /// and is re-read before and after writing; if it changed in the meantime,
+    /// [ClientError::HeaderChanged] is returned.
+    pub fn set_buffers_in_use(&mut self, buffers_in_use: u8) -> Result<OcsdHeader, ClientError> {
+        let raw = self.read_header_raw()?;
+        let header = OcsdHeader::from_bytes_checked(&raw)?;
+        if (
+            header.buffer_start_address,
+            header.max_option_cards,
+            header.one_option_card_size,
+        ) != (
+            self.layout.buffer_start_address,
+            self.layout.max_option_cards,
+            self.layout.one_option_card_size,
+        ) {
+            return Err(ClientError::HeaderChanged);
+        }
+        if buffers_in_use > header.max_option_cards {
+            return Err(ClientError::TooManyBuffers {
+                max_option_cards: header.max_option_cards,
+                buffers_in_use,
+            });
+        }
+        if buffers_in_use == header.buffers_in_use {
+            return Ok(header);
+        }
+
+        let (offset, patch) = OcsdHeader::buffers_in_use_patch(&raw, buffers_in_use);
+        let mut expected = raw.clone();
+        expected[offset..].copy_from_slice(&patch);
+        if self.read_header_raw()? != raw {
+            return Err(ClientError::HeaderChanged);
+        }
+        self.header_mapping.write(offset, &patch)?;
+        if self.read_header_raw()? != expected {
+            return Err(ClientError::HeaderChanged);
+        }
+        Ok(OcsdHeader::from_bytes(&expected))
     }
 }
 
 impl OcsdDeviceContext {
-    /// Read and parse this device from the OCSD buffer.
-    pub fn read(&mut self) -> OcsdDevice {
+    /// Read and parse this device from the OCSD buffer, with
+    /// [sensor_capacity](OcsdDeviceContext::sensor_capacity) sensors.
+    pub fn read(&mut self) -> Result<OcsdDevice, ClientError> {
+        Ok(OcsdDevice::from_bytes(&self.read_record()?))
+    }
+
+    /// Read and parse this device from the OCSD buffer, validating the
+    /// checksums of its header and sensors.
+    ///
+    /// A mismatch indicates that the record is corrupt, or was read while
+    /// being written.
+    pub fn read_checked(&mut self) -> Result<OcsdDevice, ClientError> {
+        Ok(OcsdDevice::from_bytes_checked(&self.read_record()?)?)
+    }
+
+    /// Read the header and sensors of this device's record, excluding any
+    /// trailing bytes too short to hold another sensor.
+    fn read_record(&mut self) -> Result<Vec<u8>, ClientError> {
+        let mut record = self.read_raw()?;
+        record.truncate(OcsdDevice::size_with_sensors(self.sensor_capacity()));
+        Ok(record)
+    }
+
+    /// Read the raw bytes of this device's record from the OCSD buffer.
+    pub fn read_raw(&mut self) -> Result<Vec<u8>, ClientError> {
         let mut device_data: Vec<u8> = vec![0x00; self.device_size as usize];
-        self.mapping.copy_into_slice(&mut device_data);
-        OcsdDevice::from_bytes(&device_data)
+        self.mapping.read(0, &mut device_data)?;
+        Ok(device_data)
+    }
+
+    /// Number of sensors which fit in this device's record.
+    pub fn sensor_capacity(&self) -> usize {
+        OcsdDevice::sensor_capacity(self.device_size as usize)
     }
 
     /// Replace the device data in the OCSD buffer with that provided.
-    pub fn write(&mut self, device: &OcsdDevice) {
-        self.mapping.copy_from_slice(&device.to_bytes());
+    ///
+    /// If the device has fewer sensors than fit in the record, the remainder
+    /// of the record is filled with null sensors. Returns an error if it has
+    /// more.
+    ///
+    /// The record is not read back; see
+    /// [write_verified](OcsdDeviceContext::write_verified).
+    pub fn write(&mut self, device: &OcsdDevice) -> Result<(), ClientError> {
+        self.write_record(device).map(|_| ())
+    }
+
+    /// Replace the device data in the OCSD buffer with that provided, then
+    /// read the record back and check that it is valid and matches.
+    ///
+    /// Returns [ClientError::Verify] if another writer, such as iLO, changed
+    /// the record in the meantime, describing the fields which differ.
+    pub fn write_verified(&mut self, device: &OcsdDevice) -> Result<(), ClientError> {
+        let written = self.write_record(device)?;
+        let read = self.read_record()?;
+        if read == written {
+            return Ok(());
+        }
+        OcsdDevice::from_bytes_checked(&read).map_err(VerifyError::Invalid)?;
+        Err(VerifyError::Mismatch(field_mismatches(&written, &read)).into())
+    }
+
+    /// Writes the device padded to the slot's sensor capacity, returning the
+    /// bytes written.
+    fn write_record(&mut self, device: &OcsdDevice) -> Result<Vec<u8>, ClientError> {
+        let capacity = self.sensor_capacity();
+        if device.sensors.len() > capacity {
+            return Err(ClientError::TooManySensors {
+                capacity,
+                sensors: device.sensors.len(),
+            });
+        }
+        let mut bytes = device.to_bytes();
+        bytes.resize(OcsdDevice::size_with_sensors(capacity), 0x00);
+        self.mapping.write(0, &bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Lists the fields which differ between two device records of equal length.
+/// Differences outside of any field, such as in padding, are reported by the
+/// offset of the first differing byte.
+fn field_mismatches(written: &[u8], read: &[u8]) -> Vec<FieldMismatch> {
+    let (written_device, read_device) = (
+        OcsdDevice::from_bytes(written),
+        OcsdDevice::from_bytes(read),
+    );
+    let mut fields = Vec::new();
+    let mut compare = |field: String, written: i64, read: i64| {
+        if written != read {
+            fields.push(FieldMismatch {
+                field,
+                written,
+                read,
+            });
+        }
+    };
+
+    let (w, r) = (&written_device.header, &read_device.header);
+    compare("version".to_string(), w.version as i64, r.version as i64);
+    compare("pci_bus".to_string(), w.pci_bus.into(), r.pci_bus.into());
+    compare(
+        "pci_device".to_string(),
+        w.pci_device.into(),
+        r.pci_device.into(),
+    );
+    compare(
+        "unknown_1".to_string(),
+        w.unknown_1.into(),
+        r.unknown_1.into(),
+    );
+    compare(
+        "unknown_2".to_string(),
+        w.unknown_2.into(),
+        r.unknown_2.into(),
+    );
+    compare(
+        "flags_caps".to_string(),
+        w.flags_caps.bits().into(),
+        r.flags_caps.bits().into(),
+    );
+    for (i, (w, r)) in w.unknown_3.iter().zip(&r.unknown_3).enumerate() {
+        compare(format!("unknown_3[{}]", i), (*w).into(), (*r).into());
+    }
+
+    for (i, (w, r)) in written_device
+        .sensors
+        .iter()
+        .zip(&read_device.sensors)
+        .enumerate()
+    {
+        let name = |field: &str| format!("sensor {} {}", i, field);
+        compare(name("type"), w.sensor_type as i64, r.sensor_type as i64);
+        compare(
+            name("location"),
+            w.sensor_location as i64,
+            r.sensor_location as i64,
+        );
+        compare(
+            name("configuration"),
+            w.configuration.bits().into(),
+            r.configuration.bits().into(),
+        );
+        compare(
+            name("status"),
+            w.status.bits().into(),
+            r.status.bits().into(),
+        );
+        compare(
+            name("max_continuous_threshold"),
+            w.max_continuous_threshold.degrees().into(),
+            r.max_continuous_threshold.degrees().into(),
+        );
+        compare(
+            name("caution_threshold"),
+            w.caution_threshold.degrees().into(),
+            r.caution_threshold.degrees().into(),
+        );
+        compare(
+            name("reading"),
+            w.reading.degrees().into(),
+            r.reading.degrees().into(),
+        );
+        compare(
+            name("update_count"),
+            w.update_count.into(),
+            r.update_count.into(),
+        );
+    }
+
+    if fields.is_empty() {
+        if let Some(offset) = written.iter().zip(read).position(|(w, r)| w != r) {
+            fields.push(FieldMismatch {
+                field: format!("byte {:#x}", offset),
+                written: written[offset].into(),
+                read: read[offset].into(),
+            });
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::InMemoryBackend;
+
+    use crate::protocol::{
+        error::DecodeError, Celsius, DeviceVersion, OcsdDeviceFlags, OcsdDeviceHeader, OcsdSensor,
+        OcsdSensorLocation, OcsdSensorStatus, OcsdSensorType, OcsdVersion,
+    };
+
+    use super::*;
+
+    const BASE_ADDRESS: usize = 0x791f6000;
+
+    fn simulated_buffer() -> InMemoryBackend {
+        let header = OcsdHeader {
+            ocsd_version: OcsdVersion::Version2,
+            buffer_size: 4 * 0xa0,
+            max_option_cards: 4,
+            one_option_card_size: 0xa0,
+            buffer_start_address: BASE_ADDRESS as u32 + 0x40,
+            update_interval: 1,
+            buffers_in_use: 1,
+            ..Default::default()
+        };
+        let mut bytes = header.to_bytes();
+        bytes.resize(0x40 + 4 * 0xa0, 0x00);
+        InMemoryBackend::from_bytes(BASE_ADDRESS, bytes)
+    }
+
+    fn device(reading: i16) -> OcsdDevice {
+        OcsdDevice {
+            header: OcsdDeviceHeader {
+                version: DeviceVersion::Version1,
+                pci_bus: 0x04,
+                pci_device: 0x00,
+                flags_caps: OcsdDeviceFlags::HOST_REPORTED,
+                ..Default::default()
+            },
+            sensors: vec![
+                OcsdSensor {
+                    sensor_type: OcsdSensorType::Thermal,
+                    sensor_location: OcsdSensorLocation::InternalToAsic,
+                    status: OcsdSensorStatus::WithChecksum
+                        | OcsdSensorStatus::Present
+                        | OcsdSensorStatus::NotFailed,
+                    reading: Celsius::new(reading).unwrap(),
+                    bus: Some(0x04),
+                    ..Default::default()
+                },
+                Default::default(),
+                Default::default(),
+            ],
+        }
+    }
+
+    #[test]
+    fn open_context() {
+        let backend = simulated_buffer();
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        assert_eq!(context.device_mappings.len(), 4);
+
+        let mut header = context.read_header_checked().unwrap();
+        assert_eq!(header.buffers_in_use, 1);
+        header.buffers_in_use = 3;
+        context.write_header(&header).unwrap();
+        assert_eq!(context.read_header_checked().unwrap().buffers_in_use, 3);
+    }
+
+    #[test]
+    fn set_buffers_in_use() {
+        let backend = simulated_buffer();
+        let mut bytes = backend.contents();
+        // reserved words set by firmware
+        bytes[20..24].copy_from_slice(&[0xaa; 4]);
+        bytes[57] = 0xbb;
+        let backend = InMemoryBackend::from_bytes(BASE_ADDRESS, bytes.clone());
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+
+        let header = context.set_buffers_in_use(3).unwrap();
+        assert_eq!(header.buffers_in_use, 3);
+        let written = backend.contents();
+        assert_eq!(written[..56], bytes[..56]);
+        assert_eq!(written[56..58], [3, 0xbb]);
+        assert_eq!(context.read_header_checked().unwrap().buffers_in_use, 3);
+
+        assert!(matches!(
+            context.set_buffers_in_use(5),
+            Err(ClientError::TooManyBuffers {
+                max_option_cards: 4,
+                buffers_in_use: 5
+            })
+        ));
+        assert_eq!(context.set_buffers_in_use(4).unwrap().buffers_in_use, 4);
+
+        // the slot layout no longer matches the device mappings
+        let mut header = context.read_header_checked().unwrap();
+        header.max_option_cards = 2;
+        context.write_header(&header).unwrap();
+        assert!(matches!(
+            context.set_buffers_in_use(1),
+            Err(ClientError::HeaderChanged)
+        ));
+
+        // an invalid header is not updated
+        let mut region = backend.map(BASE_ADDRESS + 60, 1).unwrap();
+        region.write(0, &[0x00]).unwrap();
+        let before = backend.contents();
+        assert!(matches!(
+            context.set_buffers_in_use(1),
+            Err(ClientError::Decode(_))
+        ));
+        assert_eq!(backend.contents(), before);
+    }
+
+    #[test]
+    fn write_device() {
+        let backend = simulated_buffer();
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        context.device_mappings[2].write(&device(40)).unwrap();
+
+        let read = context.device_mappings[2].read_checked().unwrap();
+        assert_eq!(read.sensors[0].reading.degrees(), 40);
+        assert_eq!(
+            backend.contents()[0x40 + 2 * 0xa0..0x40 + 3 * 0xa0],
+            device(40).to_bytes()
+        );
+    }
+
+    #[test]
+    fn read_modify_write() {
+        let backend = simulated_buffer();
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let slot = &mut context.device_mappings[2];
+        slot.write(&device(40)).unwrap();
+
+        // sensors read without checking keep their bus, and aren't nulled
+        let mut read = slot.read().unwrap();
+        assert_eq!(read.sensors[0].bus, Some(0x04));
+        assert_eq!(read.sensors[1].bus, None);
+        read.sensors[0].reading = Celsius::new(50).unwrap();
+        slot.write(&read).unwrap();
+        assert_eq!(slot.read_checked().unwrap(), device(50));
+    }
+
+    #[test]
+    fn write_sensor_count() {
+        let backend = simulated_buffer();
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let slot = &mut context.device_mappings[1];
+        assert_eq!(slot.sensor_capacity(), 3);
+
+        // missing sensors are written as null sensors
+        slot.write(&device(40)).unwrap();
+        let mut short = device(41);
+        short.sensors.truncate(1);
+        slot.write(&short).unwrap();
+        let read = slot.read_checked().unwrap();
+        assert_eq!(read.sensors.len(), 3);
+        assert_eq!(read.sensors[0].reading.degrees(), 41);
+
+        let mut long = device(42);
+        long.sensors.push(Default::default());
+        assert!(matches!(
+            slot.write(&long),
+            Err(ClientError::TooManySensors {
+                capacity: 3,
+                sensors: 4
+            })
+        ));
+        assert_eq!(
+            slot.read_checked().unwrap().sensors[0].reading.degrees(),
+            41
+        );
+    }
+
+    /// Region which another writer overwrites right after each write.
+    struct Clobbered {
+        region: Box<dyn MemoryRegion>,
+        offset: usize,
+        data: Vec<u8>,
+    }
+
+    impl MemoryRegion for Clobbered {
+        fn len(&self) -> usize {
+            self.region.len()
+        }
+
+        fn read(&mut self, offset: usize, buf: &mut [u8]) -> std::io::Result<()> {
+            self.region.read(offset, buf)
+        }
+
+        fn write(&mut self, offset: usize, data: &[u8]) -> std::io::Result<()> {
+            self.region.write(offset, data)?;
+            self.region.write(self.offset, &self.data)
+        }
+    }
+
+    fn clobbered_slot(offset: usize, data: Vec<u8>) -> OcsdDeviceContext {
+        let backend = simulated_buffer();
+        OcsdDeviceContext {
+            mapping: Box::new(Clobbered {
+                region: backend.map(BASE_ADDRESS + 0x40, 0xa0).unwrap(),
+                offset,
+                data,
+            }),
+            device_size: 0xa0,
+        }
+    }
+
+    #[test]
+    fn write_verified() {
+        let backend = simulated_buffer();
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        context.device_mappings[1]
+            .write_verified(&device(40))
+            .unwrap();
+        assert_eq!(
+            context.device_mappings[1].read_checked().unwrap().sensors[0]
+                .reading
+                .degrees(),
+            40
+        );
+
+        // torn by a write of the sensor reading alone
+        let mut slot = clobbered_slot(0x40 + 20, vec![41]);
+        assert!(slot.write(&device(40)).is_ok());
+        assert!(matches!(
+            slot.write_verified(&device(40)),
+            Err(ClientError::Verify(VerifyError::Invalid(
+                DecodeError::BadChecksum(_)
+            )))
+        ));
+
+        // replaced by another valid record
+        let mut slot = clobbered_slot(0, device(41).to_bytes());
+        let err = slot.write_verified(&device(40)).unwrap_err();
+        assert!(matches!(
+            &err,
+            ClientError::Verify(VerifyError::Mismatch(fields)) if fields == &[FieldMismatch {
+                field: "sensor 0 reading".to_string(),
+                written: 40,
+                read: 41,
+            }]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "write verification failed: record read back differs from record written \
+             (sensor 0 reading: wrote 40, read 41)"
+        );
+
+        // a change outside of any field
+        let mut slot = clobbered_slot(1, vec![0xff]);
+        assert!(matches!(
+            slot.write_verified(&device(40)),
+            Err(ClientError::Verify(VerifyError::Mismatch(fields))) if fields[0].field == "byte 0x1"
+        ));
+    }
+
+    #[test]
+    fn invalid_header() {
+        let backend = InMemoryBackend::new(BASE_ADDRESS, 0x1000);
+        assert!(matches!(
+            OcsdContext::with_backend(&backend, BASE_ADDRESS),
+            Err(ClientError::Decode(_))
+        ));
+        assert!(matches!(
+            OcsdContext::with_backend(&backend, 0x1000),
+            Err(ClientError::Mapping(_))
+        ));
+    }
+
+    #[test]
+    fn torn_device() {
+        let backend = simulated_buffer();
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        context.device_mappings[0].write(&device(40)).unwrap();
+
+        // corrupt sensor 0 reading
+        let mut region = backend.map(BASE_ADDRESS + 0x40 + 0x40 + 20, 1).unwrap();
+        region.write(0, &[41]).unwrap();
+        assert!(context.device_mappings[0].read().is_ok());
+        assert!(matches!(
+            context.device_mappings[0].read_checked(),
+            Err(ClientError::Decode(_))
+        ));
     }
 }
@@ -0,0 +1,375 @@
+//! Reporting of device records with a continuous update count.
+//!
+//! iLO watches each sensor's update count to determine whether its reading is
+//! current, so the count should keep incrementing across restarts of the
+//! reporting process. A [Reporter] resumes the count from the record already
+//! in the OCSD buffer or, if there is none, from a state file. The state file
+//! holds a count ahead of those reported, and is only rewritten once the
+//! reported count reaches it, so that the count never goes backwards after a
+//! crash without syncing the file on every report.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{error::ClientError, OcsdDeviceContext};
+use crate::protocol::{DeviceVersion, OcsdDevice};
+
+/// Number of update counts reserved by each write of the state file, i.e.
+/// reports between writes, and the most a count resumed from the state file
+/// skips ahead of the last report.
+pub const STATE_FILE_RESERVE: u16 = 64;
+
+/// Writes device records to a single OCSD slot, setting the update count of
+/// their sensors.
+pub struct Reporter {
+    pci_bus: u8,
+    update_count: u16,
+    state_file: Option<PathBuf>,
+    /// Count held by the state file, which must be rewritten before it is
+    /// reported
+    reserved: u16,
+}
+
+impl Reporter {
+    /// Creates a reporter for the device on the provided PCI bus.
+    ///
+    /// If the slot holds a valid record for the same bus, the update count
+    /// continues from it; otherwise it starts from zero.
+    pub fn new(device_context: &mut OcsdDeviceContext, pci_bus: u8) -> Self {
+        Self {
+            pci_bus,
+            update_count: Self::buffer_update_count(device_context, pci_bus)
+                .map_or(0, |count| count.wrapping_add(1)),
+            state_file: None,
+            reserved: 0,
+        }
+    }
+
+    /// Creates a reporter for the device on the provided PCI bus, persisting
+    /// its update count to a state file.
+    ///
+    /// If the slot holds a valid record for the same bus, the update count
+    /// continues from it; otherwise it continues from the state file, if it
+    /// exists. The state file is replaced atomically with a count
+    /// [STATE_FILE_RESERVE] ahead of the next report, and again whenever the
+    /// reports reach it, so the count also survives the process being killed:
+    /// it may skip ahead by up to [STATE_FILE_RESERVE], but never goes back.
+    ///
+    /// If the count continues from the state file, but it doesn't hold a
+    /// count, e.g. because it is empty, [ClientError::State] is returned with
+    /// [InvalidData](io::ErrorKind::InvalidData); use
+    /// [recover_state_file](Reporter::recover_state_file) to continue anyway.
+    pub fn with_state_file(
+        device_context: &mut OcsdDeviceContext,
+        pci_bus: u8,
+        state_file: impl Into<PathBuf>,
+    ) -> Result<Self, ClientError> {
+        Self::open_state_file(device_context, pci_bus, state_file.into(), false)
+    }
+
+    /// Creates a reporter as [with_state_file](Reporter::with_state_file),
+    /// but replaces a state file which doesn't hold a count, continuing from
+    /// the record in the slot or, if there is none, from zero.
+    ///
+    /// Restarting from zero can make iLO see the update count go backwards,
+    /// so this should only be used once the error has been reported.
+    pub fn recover_state_file(
+        device_context: &mut OcsdDeviceContext,
+        pci_bus: u8,
+        state_file: impl Into<PathBuf>,
+    ) -> Result<Self, ClientError> {
+        Self::open_state_file(device_context, pci_bus, state_file.into(), true)
+    }
+
+    fn open_state_file(
+        device_context: &mut OcsdDeviceContext,
+        pci_bus: u8,
+        state_file: PathBuf,
+        recover: bool,
+    ) -> Result<Self, ClientError> {
+        let update_count = match Self::buffer_update_count(device_context, pci_bus) {
+            Some(count) => count.wrapping_add(1),
+            None => match read_state(&state_file) {
+                Err(e) if recover && e.kind() == io::ErrorKind::InvalidData => 0,
+                result => result.map_err(ClientError::State)?,
+            },
+        };
+        let mut reporter = Self {
+            pci_bus,
+            update_count,
+            state_file: Some(state_file),
+            reserved: update_count,
+        };
+        reporter.reserve()?;
+        Ok(reporter)
+    }
+
+    /// Returns the update count of the last valid record for `pci_bus` in the slot.
+    fn buffer_update_count(device_context: &mut OcsdDeviceContext, pci_bus: u8) -> Option<u16> {
+        let device = device_context.read_checked().ok()?;
+        if device.header.pci_bus != pci_bus
+            || !matches!(device.header.version, DeviceVersion::Version1)
+        {
+            return None;
+        }
+        device
+            .sensors
+            .iter()
+            .find(|sensor| sensor.bus.is_some())
+            .map(|sensor| sensor.update_count)
+    }
+
+    /// PCI bus of the reported device.
+    pub fn pci_bus(&self) -> u8 {
+        self.pci_bus
+    }
+
+    /// Update count of the next report.
+    pub fn update_count(&self) -> u16 {
+        self.update_count
+    }
+
+    /// Writes the device record, with the update count of all non-null sensors
+    /// set to that of this report, then increments the update count.
+    ///
+    /// With a state file, it is rewritten before the record once every
+    /// [STATE_FILE_RESERVE] reports.
+    pub fn report(
+        &mut self,
+        device_context: &mut OcsdDeviceContext,
+        device: &mut OcsdDevice,
+    ) -> Result<(), ClientError> {
+        if self.update_count == self.reserved {
+            self.reserve()?;
+        }
+        for sensor in device.sensors.iter_mut().filter(|s| s.bus.is_some()) {
+            sensor.update_count = self.update_count;
+        }
+        device_context.write(device)?;
+        self.update_count = self.update_count.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Saves a count [STATE_FILE_RESERVE] ahead of the next report.
+    fn reserve(&mut self) -> Result<(), ClientError> {
+        if let Some(path) = &self.state_file {
+            let reserved = self.update_count.wrapping_add(STATE_FILE_RESERVE);
+            write_state(path, reserved).map_err(ClientError::State)?;
+            self.reserved = reserved;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the next update count from a state file, or zero if it doesn't exist.
+fn read_state(path: &Path) -> io::Result<u16> {
+    match fs::read_to_string(path) {
+        Ok(contents) => contents.trim().parse().map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid state file {}: {}", path.display(), e),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replaces the state file by renaming a temporary file over it, so that it
+/// is never left partially written. The temporary file is synced before the
+/// rename, and the directory after it, so that the new count is on disk
+/// before any count it reserves is reported.
+fn write_state(path: &Path, update_count: u16) -> io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut file = File::create(&temp_path)?;
+    writeln!(file, "{}", update_count)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::{backend::InMemoryBackend, OcsdContext},
+        protocol::{
+            Celsius, MemoryMapped, OcsdDeviceFlags, OcsdDeviceHeader, OcsdHeader, OcsdSensor,
+            OcsdSensorLocation, OcsdSensorStatus, OcsdSensorType, OcsdVersion,
+        },
+    };
+
+    const BASE_ADDRESS: usize = 0x791f6000;
+
+    fn context() -> OcsdContext {
+        let header = OcsdHeader {
+            ocsd_version: OcsdVersion::Version2,
+            buffer_size: 2 * 0xa0,
+            max_option_cards: 2,
+            one_option_card_size: 0xa0,
+            buffer_start_address: BASE_ADDRESS as u32 + 0x40,
+            update_interval: 1,
+            buffers_in_use: 2,
+            ..Default::default()
+        };
+        let mut bytes = header.to_bytes();
+        bytes.resize(0x40 + 2 * 0xa0, 0x00);
+        OcsdContext::with_backend(
+            &InMemoryBackend::from_bytes(BASE_ADDRESS, bytes),
+            BASE_ADDRESS,
+        )
+        .unwrap()
+    }
+
+    fn device(pci_bus: u8) -> OcsdDevice {
+        OcsdDevice {
+            header: OcsdDeviceHeader {
+                version: DeviceVersion::Version1,
+                pci_bus,
+                pci_device: 0x00,
+                flags_caps: OcsdDeviceFlags::HOST_REPORTED,
+                ..Default::default()
+            },
+            sensors: vec![
+                OcsdSensor {
+                    sensor_type: OcsdSensorType::Thermal,
+                    sensor_location: OcsdSensorLocation::InternalToAsic,
+                    status: OcsdSensorStatus::WithChecksum
+                        | OcsdSensorStatus::Present
+                        | OcsdSensorStatus::NotFailed,
+                    reading: Celsius::new(40).unwrap(),
+                    bus: Some(pci_bus),
+                    ..Default::default()
+                },
+                Default::default(),
+                Default::default(),
+            ],
+        }
+    }
+
+    fn reported_count(context: &mut OcsdContext, slot: usize) -> u16 {
+        context.device_mappings[slot]
+            .read_checked()
+            .unwrap()
+            .sensors[0]
+            .update_count
+    }
+
+    #[test]
+    fn resume_from_buffer() {
+        let mut context = context();
+        let slot = &mut context.device_mappings[1];
+        let mut reporter = Reporter::new(slot, 0x04);
+        assert_eq!(reporter.update_count(), 0);
+        for _ in 0..3 {
+            reporter.report(slot, &mut device(0x04)).unwrap();
+        }
+        assert_eq!(reported_count(&mut context, 1), 2);
+
+        let slot = &mut context.device_mappings[1];
+        assert_eq!(Reporter::new(slot, 0x04).update_count(), 3);
+        // a record for another device is not resumed
+        assert_eq!(Reporter::new(slot, 0x05).update_count(), 0);
+    }
+
+    #[test]
+    fn resume_from_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("slot1");
+        let saved = || fs::read_to_string(&state_file).unwrap();
+
+        let mut context = context();
+        let slot = &mut context.device_mappings[1];
+        let mut reporter = Reporter::with_state_file(slot, 0x04, &state_file).unwrap();
+        assert_eq!(saved(), "64\n");
+        reporter.report(slot, &mut device(0x04)).unwrap();
+        reporter.report(slot, &mut device(0x04)).unwrap();
+        // simulate a crash, leaving no chance to save state on exit
+        std::mem::forget(reporter);
+
+        // the buffer takes precedence, as it's what iLO has seen
+        fs::write(&state_file, "100\n").unwrap();
+        assert_eq!(
+            Reporter::with_state_file(slot, 0x04, &state_file)
+                .unwrap()
+                .update_count(),
+            2
+        );
+        assert_eq!(saved(), "66\n");
+
+        // e.g. after the buffer is cleared by a reboot, the count skips ahead
+        let mut context = self::context();
+        let slot = &mut context.device_mappings[1];
+        let mut reporter = Reporter::with_state_file(slot, 0x04, &state_file).unwrap();
+        assert_eq!(reporter.update_count(), 66);
+        reporter.report(slot, &mut device(0x04)).unwrap();
+        assert_eq!(reported_count(&mut context, 1), 66);
+        assert_eq!(saved(), "130\n");
+
+        // a corrupt state file doesn't matter while the buffer holds a record
+        fs::write(&state_file, "not a count").unwrap();
+        assert_eq!(
+            Reporter::with_state_file(&mut context.device_mappings[1], 0x04, &state_file)
+                .unwrap()
+                .update_count(),
+            67
+        );
+
+        // otherwise it is an error, unless recovering from it
+        fs::write(&state_file, "").unwrap();
+        let mut context = self::context();
+        let slot = &mut context.device_mappings[0];
+        match Reporter::with_state_file(slot, 0x04, &state_file) {
+            Err(ClientError::State(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            _ => panic!("expected an invalid state file error"),
+        }
+        assert_eq!(saved(), "");
+        let reporter = Reporter::recover_state_file(slot, 0x04, &state_file).unwrap();
+        assert_eq!(reporter.update_count(), 0);
+        assert_eq!(saved(), "64\n");
+    }
+
+    #[test]
+    fn reserve_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("slot1");
+        let mut context = context();
+        let slot = &mut context.device_mappings[1];
+        let mut reporter = Reporter::with_state_file(slot, 0x04, &state_file).unwrap();
+
+        // only rewritten once the reserved count is reached
+        for _ in 0..STATE_FILE_RESERVE {
+            reporter.report(slot, &mut device(0x04)).unwrap();
+            assert_eq!(fs::read_to_string(&state_file).unwrap(), "64\n");
+        }
+        reporter.report(slot, &mut device(0x04)).unwrap();
+        assert_eq!(reported_count(&mut context, 1), 64);
+        assert_eq!(fs::read_to_string(&state_file).unwrap(), "128\n");
+    }
+
+    #[test]
+    fn wrap_update_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("slot0");
+        fs::write(&state_file, format!("{}\n", u16::MAX)).unwrap();
+
+        let mut context = context();
+        let slot = &mut context.device_mappings[0];
+        let mut reporter = Reporter::with_state_file(slot, 0x04, &state_file).unwrap();
+        reporter.report(slot, &mut device(0x04)).unwrap();
+        assert_eq!(reporter.update_count(), 0);
+        reporter.report(slot, &mut device(0x04)).unwrap();
+        assert_eq!(reported_count(&mut context, 0), 0);
+
+        let slot = &mut context.device_mappings[0];
+        assert_eq!(Reporter::new(slot, 0x04).update_count(), 1);
+    }
+}
@@ -0,0 +1,335 @@
+//! Cleaning up the OCSD buffer when a reporter exits.
+//!
+//! When a reporter stops, the records it wrote stay in the buffer, and iLO
+//! keeps showing their last readings. A [ShutdownGuard] owns the
+//! [OcsdContext] of a reporter, and applies a [ShutdownPolicy] when it is
+//! dropped, including while unwinding from a panic. With the `signals`
+//! feature, `stop_on_signals` provides a flag which is set on SIGINT or
+//! SIGTERM, so that the reporter's loop can return and drop the guard.
+//!
+//! Nothing is run if the process is killed, aborts on panic, or exits without
+//! dropping the guard, e.g. with [std::process::exit].
+
+use std::{
+    fmt::Display,
+    ops::{Deref, DerefMut},
+};
+
+use super::{error::ClientError, OcsdContext};
+use crate::protocol::{OcsdHeader, OcsdSensorState};
+
+/// What a [ShutdownGuard] does to the OCSD buffer when it is dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ShutdownPolicy {
+    /// Leave the header and the last records written in place
+    #[default]
+    LeaveAsIs,
+    /// Restore the header as it was when the guard was created, so that slots
+    /// enabled by the reporter are no longer in use. If fields other than
+    /// buffers in use have changed since, e.g. because iLO or firmware
+    /// rewrote the header, it is left as is. Records are left in place.
+    RestoreHeader,
+    /// Rewrite the records of the reported slots with all sensors
+    /// [failed](OcsdSensorState::Failed) and an incremented update count
+    FailSensors,
+}
+
+/// Shows the snake_case name used in serialised output, e.g. `restore_header`.
+impl Display for ShutdownPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::LeaveAsIs => "leave_as_is",
+            Self::RestoreHeader => "restore_header",
+            Self::FailSensors => "fail_sensors",
+        })
+    }
+}
+
+/// Owns an [OcsdContext], and applies a [ShutdownPolicy] to the OCSD buffer
+/// when dropped.
+///
+/// The context is accessed through the guard, which dereferences to it.
+/// Errors while applying the policy on drop are printed to stderr; use
+/// [shutdown](ShutdownGuard::shutdown) to handle them instead.
+///
+/// # Examples
+/// ```
+/// use ocsd::client::{
+///     backend::InMemoryBackend,
+///     shutdown::{ShutdownGuard, ShutdownPolicy},
+///     OcsdContext,
+/// };
+/// use ocsd::protocol::{MemoryMapped, OcsdHeader, OcsdVersion};
+///
+/// let header = OcsdHeader {
+///     ocsd_version: OcsdVersion::Version2,
+///     max_option_cards: 4,
+///     one_option_card_size: 0xa0,
+///     buffer_start_address: 0x1040,
+///     ..Default::default()
+/// };
+/// let mut bytes = header.to_bytes();
+/// bytes.resize(0x40 + 4 * 0xa0, 0x00);
+/// let backend = InMemoryBackend::from_bytes(0x1000, bytes);
+///
+/// let context = OcsdContext::with_backend(&backend, 0x1000).unwrap();
+/// let mut guard = ShutdownGuard::new(context, ShutdownPolicy::RestoreHeader, [2]).unwrap();
+/// guard.set_buffers_in_use(3).unwrap();
+/// drop(guard);
+///
+/// let mut context = OcsdContext::with_backend(&backend, 0x1000).unwrap();
+/// assert_eq!(context.read_header().unwrap().buffers_in_use, 0);
+/// ```
+pub struct ShutdownGuard {
+    /// [None] once the policy has been applied or the guard disarmed
+    context: Option<OcsdContext>,
+    policy: ShutdownPolicy,
+    header: OcsdHeader,
+    header_raw: Vec<u8>,
+    slots: Vec<u8>,
+}
+
+impl ShutdownGuard {
+    /// Creates a guard for a reporter writing the provided device slots,
+    /// reading the header to be restored.
+    pub fn new(
+        mut context: OcsdContext,
+        policy: ShutdownPolicy,
+        slots: impl IntoIterator<Item = u8>,
+    ) -> Result<Self, ClientError> {
+        let header_raw = context.read_header_raw()?;
+        let header = OcsdHeader::from_bytes_checked(&header_raw)?;
+        Ok(Self {
+            context: Some(context),
+            policy,
+            header,
+            header_raw,
+            slots: slots.into_iter().collect(),
+        })
+    }
+
+    /// Policy applied when the guard is dropped.
+    pub fn policy(&self) -> ShutdownPolicy {
+        self.policy
+    }
+
+    /// Header read when the guard was created.
+    pub fn header(&self) -> &OcsdHeader {
+        &self.header
+    }
+
+    /// Applies the policy now, returning any error.
+    pub fn shutdown(mut self) -> Result<(), ClientError> {
+        match self.context.take() {
+            Some(mut context) => self.apply(&mut context),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the context without applying the policy.
+    pub fn into_inner(mut self) -> OcsdContext {
+        self.context
+            .take()
+            .expect("context is only taken on shutdown")
+    }
+
+    fn apply(&self, context: &mut OcsdContext) -> Result<(), ClientError> {
+        match self.policy {
+            ShutdownPolicy::LeaveAsIs => Ok(()),
+            ShutdownPolicy::RestoreHeader => {
+                // only the reporter's change of buffers in use may be undone
+                let mut current = context.read_header_raw()?;
+                let (offset, patch) =
+                    OcsdHeader::buffers_in_use_patch(&current, self.header.buffers_in_use);
+                current[offset..].copy_from_slice(&patch);
+                if current != self.header_raw {
+                    return Err(ClientError::HeaderChanged);
+                }
+                Ok(context.header_mapping.write(0, &self.header_raw)?)
+            }
+            ShutdownPolicy::FailSensors => {
+                for &slot in &self.slots {
+                    let Some(device_context) = context.device_mappings.get_mut(slot as usize)
+                    else {
+                        continue;
+                    };
+                    // leave records which aren't valid, e.g. cleared by iLO
+                    let Ok(mut device) = device_context.read_checked() else {
+                        continue;
+                    };
+                    for sensor in device.sensors.iter_mut().filter(|s| s.bus.is_some()) {
+                        sensor.set_state(OcsdSensorState::Failed);
+                        sensor.update_count = sensor.update_count.wrapping_add(1);
+                    }
+                    device_context.write(&device)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Deref for ShutdownGuard {
+    type Target = OcsdContext;
+
+    fn deref(&self) -> &OcsdContext {
+        self.context
+            .as_ref()
+            .expect("context is only taken on shutdown")
+    }
+}
+
+impl DerefMut for ShutdownGuard {
+    fn deref_mut(&mut self) -> &mut OcsdContext {
+        self.context
+            .as_mut()
+            .expect("context is only taken on shutdown")
+    }
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        if let Some(mut context) = self.context.take() {
+            if let Err(e) = self.apply(&mut context) {
+                eprintln!("error: unable to apply shutdown policy: {}", e);
+            }
+        }
+    }
+}
+
+/// Returns a flag which is set when the process receives SIGINT or SIGTERM,
+/// e.g. to be passed to the daemon's `run`.
+///
+/// The signals no longer terminate the process, so the flag must be checked.
+/// This can only be called once per process.
+#[cfg(feature = "signals")]
+pub fn stop_on_signals() -> std::io::Result<std::sync::Arc<std::sync::atomic::AtomicBool>> {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    ctrlc::set_handler(move || handler_stop.store(true, Ordering::Relaxed))
+        .map_err(std::io::Error::other)?;
+    Ok(stop)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::*;
+    use crate::{
+        client::backend::InMemoryBackend,
+        protocol::{
+            Celsius, MemoryMapped, OcsdDevice, OcsdDeviceHeader, OcsdSensor, OcsdSensorStatus,
+            OcsdVersion,
+        },
+    };
+
+    const BASE_ADDRESS: usize = 0x791f6000;
+
+    fn simulated_buffer() -> InMemoryBackend {
+        let header = OcsdHeader {
+            ocsd_version: OcsdVersion::Version2,
+            buffer_size: 4 * 0xa0,
+            max_option_cards: 4,
+            one_option_card_size: 0xa0,
+            buffer_start_address: BASE_ADDRESS as u32 + 0x40,
+            update_interval: 1,
+            buffers_in_use: 1,
+            ..Default::default()
+        };
+        let mut bytes = header.to_bytes();
+        bytes.resize(0x40 + 4 * 0xa0, 0x00);
+        InMemoryBackend::from_bytes(BASE_ADDRESS, bytes)
+    }
+
+    /// Enables and writes slot 2 through a guard with the provided policy.
+    fn report(backend: &InMemoryBackend, policy: ShutdownPolicy) -> ShutdownGuard {
+        let context = OcsdContext::with_backend(backend, BASE_ADDRESS).unwrap();
+        let mut guard = ShutdownGuard::new(context, policy, [2]).unwrap();
+        guard.set_buffers_in_use(3).unwrap();
+        let device = OcsdDevice {
+            header: OcsdDeviceHeader::host_reported(0x04, 0x00),
+            sensors: vec![OcsdSensor {
+                status: OcsdSensorStatus::WithChecksum
+                    | OcsdSensorStatus::Present
+                    | OcsdSensorStatus::NotFailed,
+                reading: Celsius::new(45).unwrap(),
+                update_count: 7,
+                bus: Some(0x04),
+                ..Default::default()
+            }],
+        };
+        guard.device_mappings[2].write(&device).unwrap();
+        guard
+    }
+
+    fn read_back(backend: &InMemoryBackend) -> (OcsdHeader, OcsdDevice) {
+        let mut context = OcsdContext::with_backend(backend, BASE_ADDRESS).unwrap();
+        (
+            context.read_header_checked().unwrap(),
+            context.device_mappings[2].read_checked().unwrap(),
+        )
+    }
+
+    #[test]
+    fn policies() {
+        let backend = simulated_buffer();
+        drop(report(&backend, ShutdownPolicy::LeaveAsIs));
+        let (header, device) = read_back(&backend);
+        assert_eq!(header.buffers_in_use, 3);
+        assert_eq!(device.sensors[0].state(), OcsdSensorState::Ok);
+
+        let backend = simulated_buffer();
+        report(&backend, ShutdownPolicy::RestoreHeader)
+            .shutdown()
+            .unwrap();
+        let (header, device) = read_back(&backend);
+        assert_eq!(header.buffers_in_use, 1);
+        assert_eq!(device.sensors[0].state(), OcsdSensorState::Ok);
+
+        let backend = simulated_buffer();
+        drop(report(&backend, ShutdownPolicy::FailSensors));
+        let (header, device) = read_back(&backend);
+        assert_eq!(header.buffers_in_use, 3);
+        assert_eq!(device.sensors[0].state(), OcsdSensorState::Failed);
+        assert_eq!(device.sensors[0].update_count, 8);
+        assert!(device.sensors[1].bus.is_none());
+
+        let backend = simulated_buffer();
+        report(&backend, ShutdownPolicy::RestoreHeader).into_inner();
+        assert_eq!(read_back(&backend).0.buffers_in_use, 3);
+
+        // the header was rewritten by firmware since the guard was created
+        let backend = simulated_buffer();
+        let mut guard = report(&backend, ShutdownPolicy::RestoreHeader);
+        let mut header = guard.read_header_checked().unwrap();
+        header.update_interval = 2;
+        guard.write_header(&header).unwrap();
+        assert!(matches!(guard.shutdown(), Err(ClientError::HeaderChanged)));
+        assert_eq!(read_back(&backend).0, header);
+    }
+
+    #[test]
+    fn panic() {
+        let backend = simulated_buffer();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = report(&backend, ShutdownPolicy::FailSensors);
+            panic!("reporter failed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            read_back(&backend).1.sensors[0].state(),
+            OcsdSensorState::Failed
+        );
+    }
+}
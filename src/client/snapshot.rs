@@ -0,0 +1,572 @@
+//! Snapshots of the complete OCSD buffer.
+//!
+//! An [OcsdSnapshot] holds the raw bytes of the header and every device slot,
+//! whether or not they are in use or valid. It can be saved as a binary image
+//! (the header followed by all slots, as laid out on the ML350 Gen9), compared
+//! with another snapshot, and written back to the buffer, e.g. to undo changes
+//! made while experimenting.
+
+use std::{fmt::Display, fs, io, path::Path};
+
+use super::{backend::MemoryBackend, error::ClientError, OcsdContext};
+use crate::protocol::{
+    error::{DecodeError, OcsdStructure},
+    MemoryMapped, OcsdDevice, OcsdDeviceHeader, OcsdHeader,
+};
+
+/// Number of times the buffer is read while waiting for it to stop changing.
+const CAPTURE_ATTEMPTS: usize = 5;
+
+/// Raw contents of the OCSD header and all device slots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OcsdSnapshot {
+    header: Vec<u8>,
+    devices: Vec<Vec<u8>>,
+}
+
+/// Region of a snapshot in which a [SnapshotDifference] occurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotRegion {
+    /// The OCSD header
+    Header,
+    /// The record of a device slot
+    Device(u8),
+}
+
+impl Display for SnapshotRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Header => write!(f, "header"),
+            Self::Device(slot) => write!(f, "slot {}", slot),
+        }
+    }
+}
+
+/// A run of consecutive bytes which differ between two snapshots.
+///
+/// If a region is longer in one snapshot, or a slot only exists in one, the
+/// extra bytes are reported with no counterpart in the other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotDifference {
+    /// Region containing the bytes
+    pub region: SnapshotRegion,
+    /// Offset of the first differing byte within the region
+    pub offset: usize,
+    /// Bytes in the original snapshot
+    pub old: Vec<u8>,
+    /// Bytes in the compared snapshot
+    pub new: Vec<u8>,
+}
+
+impl Display for SnapshotDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        write!(
+            f,
+            "{} +{:#04x}: [{}] -> [{}]",
+            self.region,
+            self.offset,
+            hex(&self.old),
+            hex(&self.new)
+        )
+    }
+}
+
+impl OcsdSnapshot {
+    /// Reads the header and all device slots from the OCSD buffer.
+    ///
+    /// The buffer may be written by iLO or a reporter while it is read, so it
+    /// is read repeatedly until two consecutive reads match. If it keeps
+    /// changing, the last read is returned, and may contain torn records.
+    pub fn capture(context: &mut OcsdContext) -> Result<Self, ClientError> {
+        let mut snapshot = Self::read(context)?;
+        for _ in 1..CAPTURE_ATTEMPTS {
+            let next = Self::read(context)?;
+            if next == snapshot {
+                break;
+            }
+            snapshot = next;
+        }
+        Ok(snapshot)
+    }
+
+    fn read(context: &mut OcsdContext) -> Result<Self, ClientError> {
+        let header = context.read_header_raw()?;
+        let devices = context
+            .device_mappings
+            .iter_mut()
+            .map(|device| device.read_raw())
+            .collect::<Result<_, _>>()?;
+        Ok(Self { header, devices })
+    }
+
+    /// Decodes a snapshot from a binary image, which must hold the header
+    /// followed by the number and size of device slots it describes.
+    pub fn from_image(image: &[u8]) -> Result<Self, DecodeError> {
+        let header_size = OcsdHeader::memory_size();
+        if image.len() < header_size {
+            return Err(DecodeError::ShortBuffer {
+                structure: OcsdStructure::Header,
+                expected: header_size,
+                actual: image.len(),
+            });
+        }
+        let (header, devices) = image.split_at(header_size);
+        let layout = OcsdHeader::from_bytes(header);
+        let slot_size = layout.one_option_card_size as usize;
+        if layout.max_option_cards > 0 && slot_size < OcsdDeviceHeader::memory_size() {
+            return Err(DecodeError::ShortBuffer {
+                structure: OcsdStructure::DeviceHeader,
+                expected: OcsdDeviceHeader::memory_size(),
+                actual: slot_size,
+            });
+        }
+
+        let expected = header_size + layout.max_option_cards as usize * slot_size;
+        if image.len() != expected {
+            let (structure, actual) = (OcsdStructure::Snapshot, image.len());
+            return Err(if actual < expected {
+                DecodeError::ShortBuffer {
+                    structure,
+                    expected,
+                    actual,
+                }
+            } else {
+                DecodeError::OversizedBuffer {
+                    structure,
+                    expected,
+                    actual,
+                }
+            });
+        }
+        Ok(Self {
+            header: header.to_vec(),
+            // slots are only empty if there are none
+            devices: devices
+                .chunks(slot_size.max(1))
+                .map(<[u8]>::to_vec)
+                .collect(),
+        })
+    }
+
+    /// Encodes the snapshot as a binary image, holding the header followed
+    /// by all device slots.
+    pub fn to_image(&self) -> Vec<u8> {
+        let mut image = self.header.clone();
+        self.devices.iter().for_each(|d| image.extend_from_slice(d));
+        image
+    }
+
+    /// Reads a snapshot from a binary image file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Self::from_image(&fs::read(path)?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid snapshot {}: {}", path.display(), e),
+            )
+        })
+    }
+
+    /// Writes the snapshot to a binary image file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_image())
+    }
+
+    /// Parses the header. Its checksum is not validated, as a snapshot may
+    /// have been taken of a corrupt buffer.
+    pub fn header(&self) -> OcsdHeader {
+        OcsdHeader::from_bytes(&self.header)
+    }
+
+    /// Raw bytes of the header.
+    pub fn header_raw(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// Number of device slots in the snapshot.
+    pub fn slots(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Parses the device in a slot, with as many sensors as fit in the slot.
+    /// Checksums are not validated.
+    pub fn device(&self, slot: u8) -> Option<OcsdDevice> {
+        self.device_raw(slot).map(OcsdDevice::from_bytes)
+    }
+
+    /// Raw bytes of a device slot.
+    pub fn device_raw(&self, slot: u8) -> Option<&[u8]> {
+        self.devices.get(slot as usize).map(Vec::as_slice)
+    }
+
+    /// Lists the bytes which differ between this snapshot and `other`,
+    /// in order of region and offset.
+    pub fn diff(&self, other: &OcsdSnapshot) -> Vec<SnapshotDifference> {
+        let mut differences = Vec::new();
+        diff_region(
+            SnapshotRegion::Header,
+            &self.header,
+            &other.header,
+            &mut differences,
+        );
+        for slot in 0..self.devices.len().max(other.devices.len()) {
+            let record = |devices: &[Vec<u8>]| devices.get(slot).cloned().unwrap_or_default();
+            diff_region(
+                SnapshotRegion::Device(slot as u8),
+                &record(&self.devices),
+                &record(&other.devices),
+                &mut differences,
+            );
+        }
+        differences
+    }
+
+    /// Writes the snapshot back to the OCSD buffer, replacing the header and
+    /// all device slots.
+    ///
+    /// Device slots are written before the header, so that the header never
+    /// refers to slots which haven't been restored yet. Returns an error if
+    /// the snapshot's slot layout differs from that of the buffer.
+    pub fn restore(&self, context: &mut OcsdContext) -> Result<(), ClientError> {
+        let (current, restored) = (context.read_header()?, self.header());
+        if (
+            current.buffer_start_address,
+            current.max_option_cards,
+            current.one_option_card_size,
+        ) != (
+            restored.buffer_start_address,
+            restored.max_option_cards,
+            restored.one_option_card_size,
+        ) {
+            return Err(ClientError::LayoutMismatch(format!(
+                "buffer has {} slots of {:#x} bytes at {:#x}, snapshot has {} slots of {:#x} bytes at {:#x}",
+                current.max_option_cards,
+                current.one_option_card_size,
+                current.buffer_start_address,
+                restored.max_option_cards,
+                restored.one_option_card_size,
+                restored.buffer_start_address
+            )));
+        }
+        if context.device_mappings.len() != self.devices.len() {
+            return Err(ClientError::LayoutMismatch(format!(
+                "buffer has {} slots, snapshot has {}",
+                context.device_mappings.len(),
+                self.devices.len()
+            )));
+        }
+
+        for (device_context, record) in context.device_mappings.iter_mut().zip(&self.devices) {
+            device_context.mapping.write(0, record)?;
+        }
+        Ok(context.header_mapping.write(0, &self.header)?)
+    }
+
+    /// Writes the snapshot back to the OCSD buffer at `base_address`, with
+    /// device slots mapped from the snapshot's own header rather than the
+    /// buffer's, so that a buffer whose header is corrupt can be restored.
+    ///
+    /// The snapshot must have been taken on the same machine, as its slots
+    /// are written wherever its header locates them. All regions are mapped
+    /// before anything is written, and device slots are written before the
+    /// header.
+    pub fn restore_with_backend(
+        &self,
+        backend: &dyn MemoryBackend,
+        base_address: usize,
+    ) -> Result<(), ClientError> {
+        let layout = self.header();
+        if self.devices.len() != layout.max_option_cards as usize
+            || self
+                .devices
+                .iter()
+                .any(|record| record.len() != layout.one_option_card_size as usize)
+        {
+            return Err(ClientError::LayoutMismatch(format!(
+                "snapshot header describes {} slots of {:#x} bytes, but it has {} slots",
+                layout.max_option_cards,
+                layout.one_option_card_size,
+                self.devices.len()
+            )));
+        }
+
+        let mut header_mapping = backend.map(base_address, self.header.len())?;
+        let mut device_mappings = (0..layout.max_option_cards)
+            .map(|slot| layout.open_device_mapping(backend, slot))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (mapping, record) in device_mappings.iter_mut().zip(&self.devices) {
+            mapping.write(0, record)?;
+        }
+        Ok(header_mapping.write(0, &self.header)?)
+    }
+}
+
+fn diff_region(
+    region: SnapshotRegion,
+    old: &[u8],
+    new: &[u8],
+    differences: &mut Vec<SnapshotDifference>,
+) {
+    let len = old.len().max(new.len());
+    let differs = |i: usize| old.get(i) != new.get(i);
+    let mut offset = 0;
+    while offset < len {
+        if !differs(offset) {
+            offset += 1;
+            continue;
+        }
+        let end = (offset..len).find(|&i| !differs(i)).unwrap_or(len);
+        let range = |bytes: &[u8]| bytes[offset.min(bytes.len())..end.min(bytes.len())].to_vec();
+        differences.push(SnapshotDifference {
+            region,
+            offset,
+            old: range(old),
+            new: range(new),
+        });
+        offset = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        client::backend::{InMemoryBackend, MemoryBackend},
+        protocol::{
+            Celsius, DeviceVersion, OcsdDeviceFlags, OcsdSensor, OcsdSensorLocation,
+            OcsdSensorStatus, OcsdSensorType, OcsdVersion,
+        },
+    };
+
+    const BASE_ADDRESS: usize = 0x791f6000;
+
+    fn header(max_option_cards: u8) -> OcsdHeader {
+        OcsdHeader {
+            ocsd_version: OcsdVersion::Version2,
+            buffer_size: max_option_cards as u16 * 0xa0,
+            max_option_cards,
+            one_option_card_size: 0xa0,
+            buffer_start_address: BASE_ADDRESS as u32 + 0x40,
+            update_interval: 1,
+            buffers_in_use: 1,
+            ..Default::default()
+        }
+    }
+
+    fn simulated_buffer(max_option_cards: u8) -> InMemoryBackend {
+        let mut bytes = header(max_option_cards).to_bytes();
+        bytes.resize(0x40 + max_option_cards as usize * 0xa0, 0x00);
+        InMemoryBackend::from_bytes(BASE_ADDRESS, bytes)
+    }
+
+    fn device(reading: i16) -> OcsdDevice {
+        OcsdDevice {
+            header: OcsdDeviceHeader {
+                version: DeviceVersion::Version1,
+                pci_bus: 0x04,
+                pci_device: 0x00,
+                flags_caps: OcsdDeviceFlags::HOST_REPORTED,
+                ..Default::default()
+            },
+            sensors: vec![OcsdSensor {
+                sensor_type: OcsdSensorType::Thermal,
+                sensor_location: OcsdSensorLocation::InternalToAsic,
+                status: OcsdSensorStatus::WithChecksum
+                    | OcsdSensorStatus::Present
+                    | OcsdSensorStatus::NotFailed,
+                reading: Celsius::new(reading).unwrap(),
+                bus: Some(0x04),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn capture_and_restore() {
+        let backend = simulated_buffer(4);
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        context.device_mappings[2].write(&device(40)).unwrap();
+
+        let snapshot = OcsdSnapshot::capture(&mut context).unwrap();
+        assert_eq!(snapshot.to_image(), backend.contents());
+        assert_eq!(snapshot.slots(), 4);
+        assert_eq!(snapshot.header().buffers_in_use, 1);
+        let captured = snapshot.device(2).unwrap();
+        assert_eq!(captured.sensors.len(), 3);
+        assert_eq!(captured.sensors[0].reading.degrees(), 40);
+
+        let mut header = context.read_header_checked().unwrap();
+        header.buffers_in_use = 3;
+        context.write_header(&header).unwrap();
+        context.device_mappings[2].write(&device(41)).unwrap();
+        context.device_mappings[3].write(&device(42)).unwrap();
+        let mut regions: Vec<_> = snapshot
+            .diff(&OcsdSnapshot::capture(&mut context).unwrap())
+            .iter()
+            .map(|d| d.region)
+            .collect();
+        regions.dedup();
+        assert_eq!(
+            regions,
+            [
+                SnapshotRegion::Header,
+                SnapshotRegion::Device(2),
+                SnapshotRegion::Device(3)
+            ]
+        );
+
+        snapshot.restore(&mut context).unwrap();
+        assert_eq!(backend.contents(), snapshot.to_image());
+        assert!(snapshot
+            .diff(&OcsdSnapshot::capture(&mut context).unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn diff() {
+        let backend = simulated_buffer(2);
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let old = OcsdSnapshot::capture(&mut context).unwrap();
+        let mut new = old.clone();
+        new.devices[1][0x10..0x12].copy_from_slice(&[0xaa, 0xbb]);
+        new.devices[1][0x14] = 0xcc;
+        new.devices.push(vec![0x01; 2]);
+
+        let differences = old.diff(&new);
+        assert_eq!(
+            differences,
+            [
+                SnapshotDifference {
+                    region: SnapshotRegion::Device(1),
+                    offset: 0x10,
+                    old: vec![0x00, 0x00],
+                    new: vec![0xaa, 0xbb],
+                },
+                SnapshotDifference {
+                    region: SnapshotRegion::Device(1),
+                    offset: 0x14,
+                    old: vec![0x00],
+                    new: vec![0xcc],
+                },
+                SnapshotDifference {
+                    region: SnapshotRegion::Device(2),
+                    offset: 0,
+                    old: vec![],
+                    new: vec![0x01, 0x01],
+                },
+            ]
+        );
+        assert_eq!(
+            differences[0].to_string(),
+            "slot 1 +0x10: [00 00] -> [aa bb]"
+        );
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn image() {
+        let backend = simulated_buffer(2);
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        context.device_mappings[1].write(&device(40)).unwrap();
+        let snapshot = OcsdSnapshot::capture(&mut context).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ocsd.img");
+        snapshot.save(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap().len(), 0x40 + 2 * 0xa0);
+        assert_eq!(OcsdSnapshot::load(&path).unwrap(), snapshot);
+
+        let image = snapshot.to_image();
+        assert!(matches!(
+            OcsdSnapshot::from_image(&image[..0x20]),
+            Err(DecodeError::ShortBuffer {
+                structure: OcsdStructure::Header,
+                ..
+            })
+        ));
+        assert_eq!(
+            OcsdSnapshot::from_image(&image[..image.len() - 1]),
+            Err(DecodeError::ShortBuffer {
+                structure: OcsdStructure::Snapshot,
+                expected: image.len(),
+                actual: image.len() - 1,
+            })
+        );
+        let mut long = image.clone();
+        long.push(0x00);
+        assert!(matches!(
+            OcsdSnapshot::from_image(&long),
+            Err(DecodeError::OversizedBuffer { .. })
+        ));
+        fs::write(&path, &long).unwrap();
+        assert_eq!(
+            OcsdSnapshot::load(&path).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn restore_layout_mismatch() {
+        let backend = simulated_buffer(2);
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let other = simulated_buffer(4);
+        let snapshot =
+            OcsdSnapshot::capture(&mut OcsdContext::with_backend(&other, BASE_ADDRESS).unwrap())
+                .unwrap();
+
+        let before = backend.contents();
+        assert!(matches!(
+            snapshot.restore(&mut context),
+            Err(ClientError::LayoutMismatch(_))
+        ));
+        assert_eq!(backend.contents(), before);
+
+        // the header was changed after the context was opened
+        let mut region = backend.map(BASE_ADDRESS, 0x40).unwrap();
+        region.write(0, &header(4).to_bytes()).unwrap();
+        assert!(matches!(
+            snapshot.restore(&mut context),
+            Err(ClientError::LayoutMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn restore_corrupt_header() {
+        let backend = simulated_buffer(4);
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        context.device_mappings[2].write(&device(40)).unwrap();
+        let snapshot = OcsdSnapshot::capture(&mut context).unwrap();
+        drop(context);
+
+        let mut region = backend.map(BASE_ADDRESS, 0x40).unwrap();
+        region.write(0, &[0xff; 0x40]).unwrap();
+        let mut region = backend.map(BASE_ADDRESS + 0x40 + 2 * 0xa0, 0xa0).unwrap();
+        region.write(0, &[0x00; 0xa0]).unwrap();
+        assert!(OcsdContext::with_backend(&backend, BASE_ADDRESS).is_err());
+
+        snapshot
+            .restore_with_backend(&backend, BASE_ADDRESS)
+            .unwrap();
+        assert_eq!(backend.contents(), snapshot.to_image());
+        let mut context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let restored = context.device_mappings[2].read_checked().unwrap();
+        assert_eq!(restored.sensors[0].reading.degrees(), 40);
+
+        // the slots don't match the snapshot's header
+        let mut truncated = snapshot.clone();
+        truncated.devices.pop();
+        let before = backend.contents();
+        assert!(matches!(
+            truncated.restore_with_backend(&backend, BASE_ADDRESS),
+            Err(ClientError::LayoutMismatch(_))
+        ));
+        assert_eq!(backend.contents(), before);
+    }
+}
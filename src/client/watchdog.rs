@@ -0,0 +1,197 @@
+//! Watchdog reporting sensors whose source has stopped producing samples.
+//!
+//! iLO trusts the last reading written for a sensor as long as its update
+//! count keeps incrementing, so a reporter which keeps writing records while
+//! its temperature source is stuck would have iLO cool against stale data. A
+//! [Watchdog] tracks when each sensor's source last produced a sample: a
+//! sensor without a fresh sample keeps its last reading for a grace period,
+//! so that a single missed sample doesn't show up in iLO as a failure, and
+//! once its source has timed out, is reported as
+//! [failed](OcsdSensorState::Failed) or with a fail-safe reading.
+
+use std::time::{Duration, Instant};
+
+use crate::protocol::{Celsius, OcsdSensor, OcsdSensorState};
+
+/// What a [Watchdog] reports for a sensor whose source has timed out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutAction {
+    /// Report the sensor as [failed](OcsdSensorState::Failed)
+    #[default]
+    Failed,
+    /// Report the sensor as [ok](OcsdSensorState::Ok) with the provided
+    /// reading, which should be high enough that iLO ramps up the fans
+    FailSafe(Celsius),
+}
+
+#[derive(Clone, Copy)]
+struct Sample {
+    reading: Celsius,
+    time: Instant,
+    /// Set when fed, and cleared when applied to a sensor
+    fresh: bool,
+}
+
+/// Tracks the samples of a device's sensors, and sets the reading and state
+/// of each sensor before it is written.
+pub struct Watchdog {
+    timeout: Duration,
+    action: TimeoutAction,
+    samples: Vec<Option<Sample>>,
+}
+
+impl Watchdog {
+    /// Creates a watchdog for `sensors` sensors, whose sources time out when
+    /// they haven't produced a sample for `intervals` update intervals.
+    pub fn new(
+        sensors: usize,
+        update_interval: Duration,
+        intervals: u32,
+        action: TimeoutAction,
+    ) -> Self {
+        Self {
+            timeout: update_interval.saturating_mul(intervals),
+            action,
+            samples: vec![None; sensors],
+        }
+    }
+
+    /// Time after the last sample at which a source times out.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Records a fresh sample of sensor `index`. Samples of sensors beyond
+    /// those the watchdog was created for are ignored.
+    pub fn feed(&mut self, index: usize, reading: Celsius) {
+        self.feed_at(index, reading, Instant::now());
+    }
+
+    /// Records a fresh sample of sensor `index`, taken at `time`.
+    pub fn feed_at(&mut self, index: usize, reading: Celsius, time: Instant) {
+        if let Some(sample) = self.samples.get_mut(index) {
+            *sample = Some(Sample {
+                reading,
+                time,
+                fresh: true,
+            });
+        }
+    }
+
+    /// Sets the reading and state of every non-null sensor from its samples;
+    /// see [apply_at](Watchdog::apply_at).
+    pub fn apply(&mut self, sensors: &mut [OcsdSensor]) {
+        self.apply_at(sensors, Instant::now());
+    }
+
+    /// Sets the reading and state of every non-null sensor from its samples,
+    /// as of `now`:
+    ///
+    /// * a sensor fed since the last call is [ok](OcsdSensorState::Ok), with
+    ///   the fed reading;
+    /// * otherwise, a sensor whose last sample is more recent than the
+    ///   [timeout](Watchdog::timeout) is still ok, with the last reading;
+    /// * otherwise, including if it was never fed, the sensor is reported as
+    ///   the [TimeoutAction] requires.
+    pub fn apply_at(&mut self, sensors: &mut [OcsdSensor], now: Instant) {
+        for (sensor, sample) in sensors.iter_mut().zip(&mut self.samples) {
+            if sensor.bus.is_none() {
+                continue;
+            }
+            let (reading, state) = match sample {
+                Some(sample) if sample.fresh => {
+                    sample.fresh = false;
+                    (sample.reading, OcsdSensorState::Ok)
+                }
+                Some(sample) if now.saturating_duration_since(sample.time) < self.timeout => {
+                    (sample.reading, OcsdSensorState::Ok)
+                }
+                _ => match self.action {
+                    TimeoutAction::Failed => (Celsius::default(), OcsdSensorState::Failed),
+                    TimeoutAction::FailSafe(reading) => (reading, OcsdSensorState::Ok),
+                },
+            };
+            sensor.reading = reading;
+            sensor.set_state(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::OcsdSensorStatus;
+
+    fn sensors() -> Vec<OcsdSensor> {
+        vec![
+            OcsdSensor {
+                bus: Some(0x04),
+                ..Default::default()
+            };
+            2
+        ]
+    }
+
+    #[test]
+    fn grace_then_failed() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(1);
+        let mut watchdog = Watchdog::new(2, interval, 3, TimeoutAction::Failed);
+        assert_eq!(watchdog.timeout(), Duration::from_secs(3));
+
+        let mut sensors = sensors();
+        watchdog.feed_at(0, Celsius::new(45).unwrap(), start);
+        watchdog.apply_at(&mut sensors, start);
+        assert_eq!(sensors[0].state(), OcsdSensorState::Ok);
+        assert_eq!(sensors[0].reading.degrees(), 45);
+        // never fed
+        assert_eq!(sensors[1].state(), OcsdSensorState::Failed);
+
+        // iLO only sees a failure once the source has timed out
+        for elapsed in 1..3 {
+            watchdog.apply_at(&mut sensors, start + interval * elapsed);
+            assert!(sensors[0].status.contains(OcsdSensorStatus::NotFailed));
+            assert_eq!(sensors[0].valid_reading(), Some(Celsius::new(45).unwrap()));
+        }
+        watchdog.apply_at(&mut sensors, start + interval * 3);
+        assert!(!sensors[0].status.contains(OcsdSensorStatus::NotFailed));
+        assert!(sensors[0].status.contains(OcsdSensorStatus::Present));
+        assert_eq!(sensors[0].state(), OcsdSensorState::Failed);
+        assert_eq!(sensors[0].valid_reading(), None);
+
+        // recovers with the next sample
+        watchdog.feed_at(0, Celsius::new(50).unwrap(), start + interval * 4);
+        watchdog.apply_at(&mut sensors, start + interval * 4);
+        assert_eq!(sensors[0].valid_reading(), Some(Celsius::new(50).unwrap()));
+    }
+
+    #[test]
+    fn fail_safe() {
+        let start = Instant::now();
+        let fail_safe = Celsius::new(105).unwrap();
+        let mut watchdog = Watchdog::new(
+            3,
+            Duration::from_secs(1),
+            0,
+            TimeoutAction::FailSafe(fail_safe),
+        );
+        let mut sensors = sensors();
+        sensors.push(OcsdSensor::default());
+        watchdog.feed_at(0, Celsius::new(45).unwrap(), start);
+        watchdog.apply_at(&mut sensors, start);
+        assert_eq!(sensors[0].valid_reading(), Some(Celsius::new(45).unwrap()));
+        assert_eq!(sensors[1].valid_reading(), Some(fail_safe));
+        // null sensors are left alone
+        assert_eq!(sensors[2], OcsdSensor::default());
+
+        // without a grace period, a missed sample times out at once
+        watchdog.apply_at(&mut sensors, start);
+        assert_eq!(sensors[0].valid_reading(), Some(fail_safe));
+
+        // samples of sensors the watchdog doesn't track are ignored
+        let before = sensors.clone();
+        watchdog.feed_at(5, Celsius::new(45).unwrap(), start);
+        watchdog.apply_at(&mut sensors, start);
+        assert_eq!(sensors, before);
+    }
+}
@@ -0,0 +1,75 @@
+//! Configuration error types.
+
+use std::{error::Error, fmt::Display, io};
+
+use crate::daemon::error::DaemonError;
+
+/// Error returned when a configuration can't be loaded or is invalid.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file could not be read
+    Io(io::Error),
+    /// The configuration file has an extension other than `.toml`, `.yaml` or `.yml`
+    UnknownFormat(String),
+    /// The TOML configuration does not match the schema
+    Toml(toml::de::Error),
+    /// The YAML configuration does not match the schema
+    Yaml(serde_yaml::Error),
+    /// The configuration is well-formed, but its values are invalid
+    Invalid(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "unable to read configuration: {}", e),
+            Self::UnknownFormat(path) => write!(
+                f,
+                "unknown configuration format of {}: expected .toml, .yaml or .yml",
+                path
+            ),
+            Self::Toml(e) => write!(f, "invalid configuration: {}", e),
+            Self::Yaml(e) => write!(f, "invalid configuration: {}", e),
+            Self::Invalid(msg) => write!(f, "invalid configuration: {}", msg),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Toml(e) => Some(e),
+            Self::Yaml(e) => Some(e),
+            Self::UnknownFormat(_) | Self::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::Toml(value)
+    }
+}
+
+impl From<serde_yaml::Error> for ConfigError {
+    fn from(value: serde_yaml::Error) -> Self {
+        Self::Yaml(value)
+    }
+}
+
+/// Invalid daemon configurations are reported as [ConfigError::Invalid].
+impl From<DaemonError> for ConfigError {
+    fn from(value: DaemonError) -> Self {
+        match value {
+            DaemonError::Config(msg) => Self::Invalid(msg),
+            e => Self::Invalid(e.to_string()),
+        }
+    }
+}
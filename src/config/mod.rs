@@ -0,0 +1,498 @@
+//! Declarative configuration of the devices reported in each OCSD slot.
+//!
+//! A configuration lists the OCSD slots to be written, the PCI device each
+//! slot describes, and the sensors of each device along with the hwmon
+//! temperature input each sensor is read from. Configurations can be
+//! written in TOML or YAML:
+//!
+//! ```
+//! use ocsd::config::Config;
+//!
+//! let config = Config::from_toml(r#"
+//!     [[slots]]
+//!     slot = 2
+//!     pci_bus = 0x04
+//!
+//!     [[slots.sensors]]
+//!     location = "internal_to_asic"
+//!     caution_threshold = 90
+//!     max_continuous_threshold = 80
+//!     source = { chip = "amdgpu", label = "edge" }
+//! "#).unwrap();
+//! assert_eq!(config.slots[0].sensors.len(), 1);
+//! ```
+//!
+//! The equivalent YAML is:
+//!
+//! ```yaml
+//! slots:
+//!   - slot: 2
+//!     pci_bus: 0x04
+//!     sensors:
+//!       - location: internal_to_asic
+//!         caution_threshold: 90
+//!         max_continuous_threshold: 80
+//!         source: { chip: amdgpu, label: edge }
+//! ```
+
+pub mod error;
+
+use std::{fs, path::Path, path::PathBuf};
+
+use error::ConfigError;
+use serde::Deserialize;
+
+use crate::{
+    client::shutdown::ShutdownPolicy,
+    daemon::{DaemonConfig, DeviceSource, SensorSource},
+    protocol::{
+        Celsius, DeviceVersion, OcsdDevice, OcsdDeviceFlags, OcsdDeviceHeader, OcsdHeader,
+        OcsdSensor, OcsdSensorLocation, OcsdSensorStatus, OcsdSensorType, TemperatureEncoding,
+    },
+    sysfs::hwmon::{HwmonSelector, InputSelector},
+};
+
+/// Configuration of all reported OCSD slots.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Location of sysfs, which defaults to `/sys`
+    #[serde(default)]
+    pub sysfs_root: Option<PathBuf>,
+    /// Directory in which the daemon persists update counts, so that they
+    /// continue across reboots
+    #[serde(default)]
+    pub state_dir: Option<PathBuf>,
+    /// Encoding of temperatures in the OCSD buffer, e.g. `signed` or
+    /// `offset:40`, which defaults to that of the header's OCSD version; see
+    /// [TemperatureEncoding::for_version]
+    #[serde(default)]
+    pub temperature_encoding: Option<TemperatureEncoding>,
+    /// Number of update intervals for which the daemon keeps the last reading
+    /// of a sensor whose source can't be read, before reporting it as failed,
+    /// which defaults to 3
+    #[serde(default)]
+    pub stale_intervals: Option<u32>,
+    /// Reading reported instead of a failed sensor, in degrees Celsius, e.g.
+    /// a temperature high enough that iLO ramps up the fans
+    #[serde(default)]
+    pub fail_safe_reading: Option<i16>,
+    /// What the daemon does to the OCSD buffer when it exits, which defaults
+    /// to `leave_as_is`; see [ShutdownPolicy]
+    #[serde(default)]
+    pub shutdown_policy: ShutdownPolicy,
+    /// Slots to be reported
+    #[serde(default)]
+    pub slots: Vec<SlotConfig>,
+}
+
+/// Configuration of a single OCSD slot.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlotConfig {
+    /// OCSD device slot, which must be less than the header's `max_option_cards`
+    pub slot: u8,
+    /// PCI bus to which the device is attached
+    pub pci_bus: u8,
+    /// PCI device number on the bus, which defaults to 0
+    #[serde(default)]
+    pub pci_device: u8,
+    /// Flags/caps information for the device header, which defaults to
+    /// [OcsdDeviceFlags::HOST_REPORTED]
+    #[serde(default = "OcsdDeviceFlags::host_reported")]
+    pub flags_caps: OcsdDeviceFlags,
+    /// Sensors of the device
+    #[serde(default)]
+    pub sensors: Vec<SensorConfig>,
+}
+
+/// Configuration of a single sensor.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SensorConfig {
+    /// Type of sensor, which defaults to `thermal`
+    #[serde(rename = "type", default = "thermal")]
+    pub sensor_type: OcsdSensorType,
+    /// Location of the sensor on the option card, e.g. `internal_to_asic` or
+    /// `onboard_other`
+    pub location: OcsdSensorLocation,
+    /// A caution should be raised when the reading exceeds this value, in degrees Celsius
+    pub caution_threshold: i16,
+    /// Maximum allowed continuous temperature, in degrees Celsius
+    pub max_continuous_threshold: i16,
+    /// hwmon temperature input providing the sensor's readings
+    pub source: SourceConfig,
+}
+
+fn thermal() -> OcsdSensorType {
+    OcsdSensorType::Thermal
+}
+
+/// hwmon temperature input of a sensor, selected by chip name and exactly
+/// one of input index or label.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceConfig {
+    /// Name of the hwmon chip, e.g. `amdgpu`
+    pub chip: String,
+    /// Index of the input, e.g. 1 for `temp1_input`
+    #[serde(default)]
+    pub index: Option<u32>,
+    /// Label of the input, e.g. `edge`
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+impl SourceConfig {
+    /// Returns the selector for this source's hwmon input.
+    pub fn selector(&self) -> Result<HwmonSelector, ConfigError> {
+        let input = match (self.index, &self.label) {
+            (Some(index), None) => InputSelector::Index(index),
+            (None, Some(label)) => InputSelector::Label(label.clone()),
+            _ => {
+                return Err(ConfigError::Invalid(format!(
+                    "source of chip {} must have exactly one of index or label",
+                    self.chip
+                )))
+            }
+        };
+        Ok(HwmonSelector {
+            chip: self.chip.clone(),
+            input,
+        })
+    }
+}
+
+fn celsius(
+    degrees: i16,
+    encoding: TemperatureEncoding,
+    slot: u8,
+    sensor: usize,
+) -> Result<Celsius, ConfigError> {
+    encoding.encode(degrees).map_err(|e| {
+        ConfigError::Invalid(format!(
+            "slot {} sensor {}: threshold {}: {}",
+            slot, sensor, degrees, e
+        ))
+    })
+}
+
+impl Config {
+    /// Parses and checks a TOML configuration.
+    pub fn from_toml(contents: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(contents)?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Parses and checks a YAML configuration.
+    pub fn from_yaml(contents: &str) -> Result<Self, ConfigError> {
+        let config: Self = serde_yaml::from_str(contents)?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Loads a configuration file, whose format is selected by its extension:
+    /// `.toml`, `.yaml` or `.yml`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let parse = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml,
+            Some("yaml" | "yml") => Self::from_yaml,
+            _ => return Err(ConfigError::UnknownFormat(path.display().to_string())),
+        };
+        parse(&fs::read_to_string(path)?)
+    }
+
+    /// Checks the parts of the configuration which don't depend on the OCSD
+    /// header, i.e. that each source selects a single input, and
+    /// [DaemonConfig::check].
+    ///
+    /// This is done automatically when parsing a configuration.
+    pub fn check(&self) -> Result<(), ConfigError> {
+        Ok(self.daemon_config()?.check()?)
+    }
+
+    /// Checks the configuration against the OCSD header; see
+    /// [DaemonConfig::validate].
+    pub fn validate(&self, header: &OcsdHeader) -> Result<(), ConfigError> {
+        Ok(self.daemon_config()?.validate(header)?)
+    }
+
+    /// Validates the configuration against the OCSD header, and builds the
+    /// device record of every slot; see [SlotConfig::device].
+    pub fn devices(&self, header: &OcsdHeader) -> Result<Vec<(u8, OcsdDevice)>, ConfigError> {
+        self.validate(header)?;
+        let encoding = self.temperature_encoding(header);
+        self.slots
+            .iter()
+            .map(|slot| Ok((slot.slot, slot.device(encoding)?)))
+            .collect()
+    }
+
+    /// Encoding of temperatures in the buffer with the provided header: the
+    /// configured encoding, or that of the header's OCSD version.
+    pub fn temperature_encoding(&self, header: &OcsdHeader) -> TemperatureEncoding {
+        self.temperature_encoding
+            .unwrap_or_else(|| TemperatureEncoding::for_version(header.ocsd_version))
+    }
+
+    /// Converts the configuration into that of the [daemon](crate::daemon),
+    /// without validating it.
+    pub fn daemon_config(&self) -> Result<DaemonConfig, ConfigError> {
+        let mut config = DaemonConfig::default();
+        if let Some(sysfs_root) = &self.sysfs_root {
+            config.sysfs_root = sysfs_root.clone();
+        }
+        config.state_dir = self.state_dir.clone();
+        config.temperature_encoding = self.temperature_encoding;
+        if let Some(stale_intervals) = self.stale_intervals {
+            config.stale_intervals = stale_intervals;
+        }
+        config.fail_safe_reading = self.fail_safe_reading;
+        config.shutdown_policy = self.shutdown_policy;
+        for slot in &self.slots {
+            let mut sensors = Vec::new();
+            for sensor in &slot.sensors {
+                sensors.push(SensorSource {
+                    source: sensor.source.selector()?,
+                    sensor_type: sensor.sensor_type,
+                    location: sensor.location,
+                    caution_threshold: sensor.caution_threshold,
+                    max_continuous_threshold: sensor.max_continuous_threshold,
+                });
+            }
+            config.devices.push(DeviceSource {
+                slot: slot.slot,
+                pci_bus: slot.pci_bus,
+                pci_device: slot.pci_device,
+                flags_caps: slot.flags_caps,
+                sensors,
+            });
+        }
+        Ok(config)
+    }
+}
+
+impl SlotConfig {
+    /// Builds the device record for this slot, with all configured sensors
+    /// present and not failed, and thresholds in the provided encoding.
+    /// Readings and update counts are zero, and should be filled in before
+    /// writing.
+    pub fn device(&self, encoding: TemperatureEncoding) -> Result<OcsdDevice, ConfigError> {
+        let mut sensors = Vec::new();
+        for (i, sensor) in self.sensors.iter().enumerate() {
+            sensors.push(OcsdSensor {
+                sensor_type: sensor.sensor_type,
+                sensor_location: sensor.location,
+                configuration: Default::default(),
+                status: OcsdSensorStatus::WithChecksum
+                    | OcsdSensorStatus::Present
+                    | OcsdSensorStatus::NotFailed,
+                max_continuous_threshold: celsius(
+                    sensor.max_continuous_threshold,
+                    encoding,
+                    self.slot,
+                    i,
+                )?,
+                caution_threshold: celsius(sensor.caution_threshold, encoding, self.slot, i)?,
+                reading: Celsius::default(),
+                update_count: 0,
+                bus: Some(self.pci_bus),
+            });
+        }
+        Ok(OcsdDevice {
+            header: OcsdDeviceHeader {
+                version: DeviceVersion::Version1,
+                pci_bus: self.pci_bus,
+                pci_device: self.pci_device,
+                flags_caps: self.flags_caps,
+                ..Default::default()
+            },
+            sensors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{MemoryMapped, OcsdVersion};
+
+    const TOML: &str = r#"
+        sysfs_root = "/tmp/sys"
+        state_dir = "/var/lib/ocsd"
+        stale_intervals = 5
+        shutdown_policy = "fail_sensors"
+
+        [[slots]]
+        slot = 2
+        pci_bus = 0x04
+
+        [[slots.sensors]]
+        location = "internal_to_asic"
+        caution_threshold = 90
+        max_continuous_threshold = 80
+        source = { chip = "amdgpu", label = "edge" }
+
+        [[slots.sensors]]
+        type = "thermal"
+        location = "onboard_other"
+        caution_threshold = 95
+        max_continuous_threshold = 85
+        source = { chip = "amdgpu", index = 3 }
+
+        [[slots]]
+        slot = 3
+        pci_bus = 0x81
+        pci_device = 1
+        flags_caps = 0
+
+        [[slots.sensors]]
+        location = "internal_to_asic"
+        caution_threshold = 75
+        max_continuous_threshold = 70
+        source = { chip = "nvme", label = "Composite" }
+    "#;
+
+    const YAML: &str = r#"
+sysfs_root: /tmp/sys
+state_dir: /var/lib/ocsd
+stale_intervals: 5
+shutdown_policy: fail_sensors
+slots:
+  - slot: 2
+    pci_bus: 0x04
+    sensors:
+      - location: internal_to_asic
+        caution_threshold: 90
+        max_continuous_threshold: 80
+        source: { chip: amdgpu, label: edge }
+      - type: thermal
+        location: onboard_other
+        caution_threshold: 95
+        max_continuous_threshold: 85
+        source: { chip: amdgpu, index: 3 }
+  - slot: 3
+    pci_bus: 0x81
+    pci_device: 1
+    flags_caps: 0
+    sensors:
+      - location: internal_to_asic
+        caution_threshold: 75
+        max_continuous_threshold: 70
+        source: { chip: nvme, label: Composite }
+"#;
+
+    fn header(max_option_cards: u8) -> OcsdHeader {
+        OcsdHeader {
+            ocsd_version: OcsdVersion::Version2,
+            buffer_size: max_option_cards as u16 * 0xa0,
+            max_option_cards,
+            one_option_card_size: 0xa0,
+            buffer_start_address: 0x791f6040,
+            update_interval: 1,
+            buffers_in_use: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let config = Config::from_toml(TOML).unwrap();
+        assert_eq!(config, Config::from_yaml(YAML).unwrap());
+        assert_eq!(config.sysfs_root, Some(PathBuf::from("/tmp/sys")));
+        assert_eq!(config.state_dir, Some(PathBuf::from("/var/lib/ocsd")));
+        assert_eq!(config.stale_intervals, Some(5));
+        assert_eq!(config.fail_safe_reading, None);
+        assert_eq!(config.shutdown_policy, ShutdownPolicy::FailSensors);
+        assert_eq!(config.slots[0].flags_caps, OcsdDeviceFlags::HOST_REPORTED);
+        assert_eq!(config.slots[1].pci_device, 1);
+        assert_eq!(
+            config.slots[0].sensors[1].source.selector().unwrap(),
+            HwmonSelector {
+                chip: "amdgpu".to_string(),
+                input: InputSelector::Index(3),
+            }
+        );
+    }
+
+    #[test]
+    fn build_devices() {
+        let devices = Config::from_toml(TOML)
+            .unwrap()
+            .devices(&header(8))
+            .unwrap();
+        assert_eq!(devices.len(), 2);
+        let (slot, device) = &devices[0];
+        assert_eq!(*slot, 2);
+        assert_eq!(device.header.pci_bus, 0x04);
+        assert_eq!(device.sensors[1].caution_threshold.degrees(), 95);
+        assert_eq!(device.sensors[1].bus, Some(0x04));
+        assert_eq!(device.sensors.len(), 2);
+        assert!(OcsdDevice::try_from(&device.to_bytes()[..]).is_ok());
+    }
+
+    #[test]
+    fn invalid() {
+        let config = Config::from_toml(TOML).unwrap();
+        assert!(matches!(
+            config.validate(&header(3)),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        let threshold = TOML.replace("caution_threshold = 95", "caution_threshold = 300");
+        assert!(matches!(
+            Config::from_toml(&threshold),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        // fits the default unsigned encoding, but not a signed one
+        let threshold = TOML.replace("caution_threshold = 95", "caution_threshold = 200");
+        assert!(Config::from_toml(&threshold).is_ok());
+        let signed = format!("temperature_encoding = \"signed\"\n{}", threshold);
+        assert!(matches!(
+            Config::from_toml(&signed),
+            Err(ConfigError::Invalid(_))
+        ));
+        let fail_safe = format!("fail_safe_reading = 300\n{}", TOML);
+        assert!(matches!(
+            Config::from_toml(&fail_safe),
+            Err(ConfigError::Invalid(_))
+        ));
+        let invalid = format!("temperature_encoding = \"offset\"\n{}", TOML);
+        assert!(matches!(
+            Config::from_toml(&invalid),
+            Err(ConfigError::Toml(_))
+        ));
+
+        let source = TOML.replace("index = 3", "index = 3, label = \"mem\"");
+        assert!(matches!(
+            Config::from_toml(&source),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        let duplicate = TOML.replace("slot = 3", "slot = 2");
+        assert!(matches!(
+            Config::from_toml(&duplicate),
+            Err(ConfigError::Invalid(_))
+        ));
+
+        let unknown = TOML.replace("pci_device = 1", "pci_devcie = 1");
+        assert!(matches!(
+            Config::from_toml(&unknown),
+            Err(ConfigError::Toml(_))
+        ));
+
+        let location = YAML.replace("onboard_other", "somewhere");
+        assert!(matches!(
+            Config::from_yaml(&location),
+            Err(ConfigError::Yaml(_))
+        ));
+        let location = YAML.replace("onboard_other", "unknown");
+        assert!(matches!(
+            Config::from_yaml(&location),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,68 @@
+//! Daemon error types.
+
+use std::{error::Error, fmt::Display, io};
+
+use crate::client::error::ClientError;
+
+/// Error returned when a temperature source can't be read.
+///
+/// These errors are not fatal; the affected sensor keeps its last reading, and
+/// is then reported as failed, until its source can be read again.
+#[derive(Debug)]
+pub struct SourceError {
+    /// OCSD device slot of the affected sensor
+    pub slot: u8,
+    /// Index of the affected sensor within the device
+    pub sensor: usize,
+    /// Reason the source could not be read
+    pub error: io::Error,
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unable to read source of slot {} sensor {}: {}",
+            self.slot, self.sensor, self.error
+        )
+    }
+}
+
+impl Error for SourceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Error returned by the daemon.
+#[derive(Debug)]
+pub enum DaemonError {
+    /// The configuration is inconsistent with itself or the OCSD header
+    Config(String),
+    /// The OCSD buffer could not be accessed
+    Client(ClientError),
+}
+
+impl Display for DaemonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Config(msg) => write!(f, "invalid configuration: {}", msg),
+            Self::Client(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for DaemonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Config(_) => None,
+            Self::Client(e) => Some(e),
+        }
+    }
+}
+
+impl From<ClientError> for DaemonError {
+    fn from(value: ClientError) -> Self {
+        Self::Client(value)
+    }
+}
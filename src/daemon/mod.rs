@@ -0,0 +1,839 @@
+//! Daemon forwarding temperatures reported by host drivers into the OCSD buffer.
+//!
+//! Each configured OCSD device slot is associated with up to three hwmon
+//! temperature inputs. On every [tick](Daemon::tick), the inputs are read and
+//! the device record is rewritten with an incremented update count;
+//! [run](Daemon::run) does this at the header's update interval. Inputs are
+//! read on worker threads, so that a read which hangs doesn't stop the
+//! records from being written, and sensors whose source can't be read in
+//! time are reported by a [Watchdog]. When the daemon is dropped, the
+//! configured [ShutdownPolicy] is applied to the buffer.
+
+pub mod error;
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use error::{DaemonError, SourceError};
+
+use crate::{
+    client::{
+        error::ClientError,
+        reporter::Reporter,
+        shutdown::{ShutdownGuard, ShutdownPolicy},
+        watchdog::{TimeoutAction, Watchdog},
+        OcsdContext,
+    },
+    protocol::{
+        Celsius, DeviceVersion, OcsdDevice, OcsdDeviceFlags, OcsdDeviceHeader, OcsdHeader,
+        OcsdSensor, OcsdSensorLocation, OcsdSensorStatus, OcsdSensorType, TemperatureEncoding,
+    },
+    sysfs::{
+        hwmon::{self, HwmonSelector},
+        SYSFS_ROOT,
+    },
+};
+
+/// Granularity at which [Daemon::run] checks for a stop request while sleeping.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time for which [Daemon::tick] waits for sources to be read. A read taking
+/// longer, e.g. in a driver waiting on its device, is reported as timed out,
+/// and its result is used by a later tick once it completes.
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Default number of update intervals for which a sensor whose source can't
+/// be read keeps its last reading, before it is reported as failed.
+pub const DEFAULT_STALE_INTERVALS: u32 = 3;
+
+/// Source and description of a single OCSD sensor.
+pub struct SensorSource {
+    /// hwmon temperature input providing the sensor's readings
+    pub source: HwmonSelector,
+    /// Type of the sensor, which must not be unknown
+    pub sensor_type: OcsdSensorType,
+    /// Location of the sensor on the option card, which must not be unknown
+    pub location: OcsdSensorLocation,
+    /// A caution should be raised when the reading exceeds this value, in degrees Celsius
+    pub caution_threshold: i16,
+    /// Maximum allowed continuous temperature, in degrees Celsius
+    pub max_continuous_threshold: i16,
+}
+
+/// An OCSD device slot to be reported by the daemon.
+pub struct DeviceSource {
+    /// OCSD device slot to write
+    pub slot: u8,
+    /// PCI bus to which the device is attached
+    pub pci_bus: u8,
+    /// PCI device number on the bus
+    pub pci_device: u8,
+    /// Flags/caps information written to the device header
+    pub flags_caps: OcsdDeviceFlags,
+    /// Sensors of the device, no more than fit in the header's option card size
+    pub sensors: Vec<SensorSource>,
+}
+
+/// Configuration of the daemon.
+pub struct DaemonConfig {
+    /// Location of sysfs, which is `/sys` on a live system
+    pub sysfs_root: PathBuf,
+    /// Device slots to report
+    pub devices: Vec<DeviceSource>,
+    /// Directory in which the update count of each slot is persisted, as
+    /// `slot<N>`; see [Reporter::with_state_file]
+    pub state_dir: Option<PathBuf>,
+    /// Encoding of temperatures in the OCSD buffer, or [None] to use that of
+    /// the header's OCSD version
+    pub temperature_encoding: Option<TemperatureEncoding>,
+    /// Number of update intervals for which a sensor whose source can't be
+    /// read keeps its last reading, before it times out
+    pub stale_intervals: u32,
+    /// Reading reported for sensors whose source has timed out, in degrees
+    /// Celsius, or [None] to report them as failed
+    pub fail_safe_reading: Option<i16>,
+    /// What to do to the OCSD buffer when the daemon is dropped
+    pub shutdown_policy: ShutdownPolicy,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            sysfs_root: PathBuf::from(SYSFS_ROOT),
+            devices: Vec::new(),
+            state_dir: None,
+            temperature_encoding: None,
+            stale_intervals: DEFAULT_STALE_INTERVALS,
+            fail_safe_reading: None,
+            shutdown_policy: ShutdownPolicy::LeaveAsIs,
+        }
+    }
+}
+
+impl DaemonConfig {
+    /// Checks the parts of the configuration which don't depend on the OCSD
+    /// header: slots are unique, sensor types and locations are known, and
+    /// thresholds and the fail-safe reading fit in [Celsius] with the
+    /// configured temperature encoding, or the default if there is none.
+    pub fn check(&self) -> Result<(), DaemonError> {
+        self.check_with(self.temperature_encoding.unwrap_or_default())
+    }
+
+    /// Checks the configuration against the OCSD header, in addition to
+    /// [check](DaemonConfig::check): all slots must be less than
+    /// `max_option_cards`, and have no more sensors than fit in the header's
+    /// option card size.
+    pub fn validate(&self, header: &OcsdHeader) -> Result<(), DaemonError> {
+        self.check_with(self.temperature_encoding(header))?;
+        for device in &self.devices {
+            if device.slot >= header.max_option_cards {
+                return Err(DaemonError::Config(format!(
+                    "slot {} doesn't fit max number of option cards {}",
+                    device.slot, header.max_option_cards
+                )));
+            }
+            if device.sensors.len() > header.sensors_per_device() {
+                return Err(DaemonError::Config(format!(
+                    "slot {} has {} sensors, but only {} fit in option card size {:#x}",
+                    device.slot,
+                    device.sensors.len(),
+                    header.sensors_per_device(),
+                    header.one_option_card_size
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Encoding of temperatures in the buffer with the provided header: the
+    /// configured encoding, or that of the header's OCSD version.
+    pub fn temperature_encoding(&self, header: &OcsdHeader) -> TemperatureEncoding {
+        self.temperature_encoding
+            .unwrap_or_else(|| TemperatureEncoding::for_version(header.ocsd_version))
+    }
+
+    fn check_with(&self, encoding: TemperatureEncoding) -> Result<(), DaemonError> {
+        if let Some(degrees) = self.fail_safe_reading {
+            celsius(encoding, degrees, "fail-safe reading")?;
+        }
+        for (i, device) in self.devices.iter().enumerate() {
+            if self.devices[..i]
+                .iter()
+                .any(|other| other.slot == device.slot)
+            {
+                return Err(DaemonError::Config(format!(
+                    "slot {} is configured more than once",
+                    device.slot
+                )));
+            }
+            for (j, sensor) in device.sensors.iter().enumerate() {
+                let name = format!("slot {} sensor {}", device.slot, j);
+                let threshold = format!("{}: threshold", name);
+                if sensor.sensor_type == OcsdSensorType::Unknown {
+                    return Err(DaemonError::Config(format!("{}: unknown type", name)));
+                }
+                if sensor.location == OcsdSensorLocation::Unknown {
+                    return Err(DaemonError::Config(format!("{}: unknown location", name)));
+                }
+                celsius(encoding, sensor.caution_threshold, &threshold)?;
+                celsius(encoding, sensor.max_continuous_threshold, &threshold)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Encodes a configured temperature, naming it in the error.
+fn celsius(
+    encoding: TemperatureEncoding,
+    degrees: i16,
+    name: &str,
+) -> Result<Celsius, DaemonError> {
+    encoding
+        .encode(degrees)
+        .map_err(|e| DaemonError::Config(format!("{} {}: {}", name, degrees, e)))
+}
+
+struct SensorState {
+    source: HwmonSelector,
+    sensor_type: OcsdSensorType,
+    location: OcsdSensorLocation,
+    caution_threshold: Celsius,
+    max_continuous_threshold: Celsius,
+    /// Path of the resolved `temp*_input`, cleared when it can't be read so
+    /// that it is resolved again, e.g. after a driver reload
+    input: Option<PathBuf>,
+    /// Set while the source is being read on a worker thread, which holds
+    /// the input path until it is done
+    reading: bool,
+}
+
+/// Result of reading a sensor's source on a worker thread.
+struct SourceReading {
+    device: usize,
+    sensor: usize,
+    input: Option<PathBuf>,
+    reading: io::Result<Celsius>,
+}
+
+/// Reads a source, resolving its input first if needed, and returns the
+/// input path to be kept if the read succeeded.
+fn read_source(
+    source: &HwmonSelector,
+    sysfs_root: &Path,
+    input: Option<PathBuf>,
+    encoding: TemperatureEncoding,
+) -> (Option<PathBuf>, io::Result<Celsius>) {
+    let path = match input {
+        Some(path) => path,
+        None => match source.resolve(sysfs_root) {
+            Ok(input) => input.path,
+            Err(e) => return (None, Err(e)),
+        },
+    };
+    match hwmon::read_temperature_with(&path, encoding) {
+        Ok(reading) => (Some(path), Ok(reading)),
+        Err(e) => (None, Err(e)),
+    }
+}
+
+struct DeviceState {
+    slot: u8,
+    header: OcsdDeviceHeader,
+    sensors: Vec<SensorState>,
+    reporter: Reporter,
+    watchdog: Watchdog,
+}
+
+/// Periodically forwards hwmon temperatures into OCSD device records.
+pub struct Daemon {
+    context: ShutdownGuard,
+    sysfs_root: PathBuf,
+    devices: Vec<DeviceState>,
+    update_interval: Duration,
+    temperature_encoding: TemperatureEncoding,
+    sender: Sender<SourceReading>,
+    receiver: Receiver<SourceReading>,
+}
+
+impl Daemon {
+    /// Creates a daemon writing to the provided OCSD buffer.
+    ///
+    /// The configuration is validated against the OCSD header, and
+    /// `buffers_in_use` is raised if needed so that all configured slots are
+    /// in use. Update counts continue from any valid records already present
+    /// for the same devices, or from the state directory.
+    ///
+    /// The header is read before raising `buffers_in_use`, so that
+    /// [ShutdownPolicy::RestoreHeader] restores it as it was.
+    pub fn new(mut context: OcsdContext, config: DaemonConfig) -> Result<Self, DaemonError> {
+        let header = context.read_header_checked()?;
+        config.validate(&header)?;
+        let temperature_encoding = config.temperature_encoding(&header);
+        let update_interval = Duration::from_secs(header.update_interval.max(1) as u64);
+        let timeout_action = match config.fail_safe_reading {
+            Some(degrees) => TimeoutAction::FailSafe(celsius(
+                temperature_encoding,
+                degrees,
+                "fail-safe reading",
+            )?),
+            None => TimeoutAction::Failed,
+        };
+        let mut devices: Vec<DeviceState> = Vec::new();
+
+        for device in config.devices {
+            let device_context = &mut context.device_mappings[device.slot as usize];
+            let reporter = match &config.state_dir {
+                Some(state_dir) => {
+                    let state_file = state_dir.join(format!("slot{}", device.slot));
+                    match Reporter::with_state_file(device_context, device.pci_bus, &state_file) {
+                        Err(ClientError::State(e)) if e.kind() == io::ErrorKind::InvalidData => {
+                            eprintln!("warning: {}; replacing it", e);
+                            Reporter::recover_state_file(
+                                device_context,
+                                device.pci_bus,
+                                state_file,
+                            )?
+                        }
+                        result => result?,
+                    }
+                }
+                None => Reporter::new(device_context, device.pci_bus),
+            };
+
+            let mut sensors = Vec::new();
+            for (i, sensor) in device.sensors.into_iter().enumerate() {
+                let name = format!("slot {} sensor {}: threshold", device.slot, i);
+                sensors.push(SensorState {
+                    caution_threshold: celsius(
+                        temperature_encoding,
+                        sensor.caution_threshold,
+                        &name,
+                    )?,
+                    max_continuous_threshold: celsius(
+                        temperature_encoding,
+                        sensor.max_continuous_threshold,
+                        &name,
+                    )?,
+                    source: sensor.source,
+                    sensor_type: sensor.sensor_type,
+                    location: sensor.location,
+                    input: None,
+                    reading: false,
+                });
+            }
+
+            let watchdog = Watchdog::new(
+                sensors.len(),
+                update_interval,
+                config.stale_intervals,
+                timeout_action,
+            );
+            devices.push(DeviceState {
+                slot: device.slot,
+                header: OcsdDeviceHeader {
+                    version: DeviceVersion::Version1,
+                    pci_bus: device.pci_bus,
+                    pci_device: device.pci_device,
+                    flags_caps: device.flags_caps,
+                    ..Default::default()
+                },
+                sensors,
+                reporter,
+                watchdog,
+            });
+        }
+
+        let mut context = ShutdownGuard::new(
+            context,
+            config.shutdown_policy,
+            devices.iter().map(|d| d.slot),
+        )?;
+        if let Some(max_slot) = devices.iter().map(|d| d.slot).max() {
+            if header.buffers_in_use <= max_slot {
+                context.set_buffers_in_use(max_slot + 1)?;
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        Ok(Self {
+            context,
+            sysfs_root: config.sysfs_root,
+            devices,
+            update_interval,
+            temperature_encoding,
+            sender,
+            receiver,
+        })
+    }
+
+    /// Interval at which [run](Daemon::run) updates the OCSD buffer, taken
+    /// from the OCSD header.
+    pub fn update_interval(&self) -> Duration {
+        self.update_interval
+    }
+
+    /// Reads all sources and writes every configured device record once.
+    ///
+    /// Sources are read on worker threads; one which isn't read within half a
+    /// second is reported as timed out, and no new read of it is started
+    /// until the current one completes. Sensors whose source can't be read
+    /// keep their last reading until the
+    /// source times out after
+    /// [stale_intervals](DaemonConfig::stale_intervals), and are then written as
+    /// [failed](crate::protocol::OcsdSensorState::Failed) or with the
+    /// [fail-safe reading](DaemonConfig::fail_safe_reading); the
+    /// corresponding errors are returned.
+    pub fn tick(&mut self) -> Result<Vec<SourceError>, DaemonError> {
+        let mut source_errors = Vec::new();
+
+        for (d, device) in self.devices.iter_mut().enumerate() {
+            for (s, state) in device.sensors.iter_mut().enumerate() {
+                if state.reading {
+                    continue;
+                }
+                state.reading = true;
+                let source = state.source.clone();
+                let input = state.input.take();
+                let sysfs_root = self.sysfs_root.clone();
+                let encoding = self.temperature_encoding;
+                let sender = self.sender.clone();
+                thread::spawn(move || {
+                    let (input, reading) = read_source(&source, &sysfs_root, input, encoding);
+                    // the daemon may have been dropped while the read hung
+                    let _ = sender.send(SourceReading {
+                        device: d,
+                        sensor: s,
+                        input,
+                        reading,
+                    });
+                });
+            }
+        }
+
+        let deadline = Instant::now() + READ_TIMEOUT;
+        while self
+            .devices
+            .iter()
+            .any(|device| device.sensors.iter().any(|state| state.reading))
+        {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let Ok(result) = self.receiver.recv_timeout(timeout) else {
+                break;
+            };
+            let device = &mut self.devices[result.device];
+            let state = &mut device.sensors[result.sensor];
+            state.reading = false;
+            state.input = result.input;
+            match result.reading {
+                Ok(reading) => device.watchdog.feed(result.sensor, reading),
+                Err(error) => source_errors.push(SourceError {
+                    slot: device.slot,
+                    sensor: result.sensor,
+                    error,
+                }),
+            }
+        }
+
+        for device in &mut self.devices {
+            let mut sensors = Vec::new();
+            for (i, state) in device.sensors.iter().enumerate() {
+                if state.reading {
+                    source_errors.push(SourceError {
+                        slot: device.slot,
+                        sensor: i,
+                        error: io::Error::new(io::ErrorKind::TimedOut, "read timed out"),
+                    });
+                }
+                sensors.push(OcsdSensor {
+                    sensor_type: state.sensor_type,
+                    sensor_location: state.location,
+                    configuration: Default::default(),
+                    status: OcsdSensorStatus::WithChecksum,
+                    max_continuous_threshold: state.max_continuous_threshold,
+                    caution_threshold: state.caution_threshold,
+                    reading: Celsius::default(),
+                    update_count: 0,
+                    bus: Some(device.header.pci_bus),
+                });
+            }
+            device.watchdog.apply(&mut sensors);
+
+            let mut record = OcsdDevice {
+                header: OcsdDeviceHeader {
+                    version: device.header.version,
+                    pci_bus: device.header.pci_bus,
+                    pci_device: device.header.pci_device,
+                    flags_caps: device.header.flags_caps,
+                    ..Default::default()
+                },
+                sensors,
+            };
+            device.reporter.report(
+                &mut self.context.device_mappings[device.slot as usize],
+                &mut record,
+            )?;
+        }
+
+        source_errors.sort_by_key(|e| (e.slot, e.sensor));
+        Ok(source_errors)
+    }
+
+    /// Updates the OCSD buffer at the update interval until `stop` is set,
+    /// e.g. by a signal handler installed with
+    /// `stop_on_signals` in [shutdown](crate::client::shutdown).
+    ///
+    /// Source errors are logged to stderr; errors accessing the OCSD buffer
+    /// stop the daemon.
+    pub fn run(&mut self, stop: &AtomicBool) -> Result<(), DaemonError> {
+        while !stop.load(Ordering::Relaxed) {
+            let next_update = Instant::now() + self.update_interval;
+            for e in self.tick()? {
+                eprintln!("warning: {}", e);
+            }
+            while !stop.load(Ordering::Relaxed) {
+                let remaining = next_update.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                thread::sleep(remaining.min(STOP_POLL_INTERVAL));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the [shutdown policy](DaemonConfig::shutdown_policy) now,
+    /// returning any error, instead of when the daemon is dropped.
+    pub fn shutdown(self) -> Result<(), DaemonError> {
+        Ok(self.context.shutdown()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use crate::{
+        client::backend::InMemoryBackend,
+        protocol::{MemoryMapped, OcsdHeader, OcsdSensorState, OcsdVersion},
+        sysfs::hwmon::{tests::add_chip, InputSelector},
+    };
+
+    const BASE_ADDRESS: usize = 0x791f6000;
+
+    fn simulated_buffer() -> InMemoryBackend {
+        let header = OcsdHeader {
+            ocsd_version: OcsdVersion::Version2,
+            buffer_size: 4 * 0xa0,
+            max_option_cards: 4,
+            one_option_card_size: 0xa0,
+            buffer_start_address: BASE_ADDRESS as u32 + 0x40,
+            update_interval: 1,
+            buffers_in_use: 1,
+            ..Default::default()
+        };
+        let mut bytes = header.to_bytes();
+        bytes.resize(0x40 + 4 * 0xa0, 0x00);
+        InMemoryBackend::from_bytes(BASE_ADDRESS, bytes)
+    }
+
+    fn config(sysfs_root: &Path, slot: u8) -> DaemonConfig {
+        DaemonConfig {
+            sysfs_root: sysfs_root.to_path_buf(),
+            devices: vec![DeviceSource {
+                slot,
+                pci_bus: 0x04,
+                pci_device: 0x00,
+                flags_caps: OcsdDeviceFlags::HOST_REPORTED,
+                sensors: vec![
+                    SensorSource {
+                        source: HwmonSelector {
+                            chip: "amdgpu".to_string(),
+                            input: InputSelector::Label("edge".to_string()),
+                        },
+                        sensor_type: OcsdSensorType::Thermal,
+                        location: OcsdSensorLocation::InternalToAsic,
+                        caution_threshold: 90,
+                        max_continuous_threshold: 80,
+                    },
+                    SensorSource {
+                        source: HwmonSelector {
+                            chip: "amdgpu".to_string(),
+                            input: InputSelector::Index(3),
+                        },
+                        sensor_type: OcsdSensorType::Thermal,
+                        location: OcsdSensorLocation::OnboardOther,
+                        caution_threshold: 95,
+                        max_continuous_threshold: 85,
+                    },
+                ],
+            }],
+            state_dir: None,
+            temperature_encoding: None,
+            // report unreadable sources as failed at once
+            stale_intervals: 0,
+            fail_safe_reading: None,
+            shutdown_policy: ShutdownPolicy::LeaveAsIs,
+        }
+    }
+
+    fn read_device(backend: &InMemoryBackend, slot: usize) -> OcsdDevice {
+        let start = 0x40 + slot * 0xa0;
+        OcsdDevice::try_from(&backend.contents()[start..start + 0xa0]).unwrap()
+    }
+
+    #[test]
+    fn forward_readings() {
+        let root = tempfile::tempdir().unwrap();
+        let chip = add_chip(
+            root.path(),
+            "hwmon3",
+            "amdgpu",
+            &[(1, Some("edge"), 45200), (3, Some("mem"), 60000)],
+        );
+        let backend = simulated_buffer();
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let mut daemon = Daemon::new(context, config(root.path(), 2)).unwrap();
+        assert_eq!(daemon.update_interval(), Duration::from_secs(1));
+        assert_eq!(
+            OcsdHeader::try_from(&backend.contents()[0..0x40])
+                .unwrap()
+                .buffers_in_use,
+            3
+        );
+
+        assert!(daemon.tick().unwrap().is_empty());
+        let device = read_device(&backend, 2);
+        assert_eq!(device.header.pci_bus, 0x04);
+        assert_eq!(device.sensors[0].reading.degrees(), 45);
+        assert_eq!(device.sensors[0].update_count, 0);
+        assert_eq!(device.sensors[1].reading.degrees(), 60);
+        assert_eq!(device.sensors[1].caution_threshold.degrees(), 95);
+        assert!(device.sensors[2].bus.is_none());
+
+        fs::write(chip.join("temp1_input"), "47000\n").unwrap();
+        fs::remove_file(chip.join("temp3_input")).unwrap();
+        let errors = daemon.tick().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].slot, errors[0].sensor), (2, 1));
+
+        let device = read_device(&backend, 2);
+        assert_eq!(device.sensors[0].reading.degrees(), 47);
+        assert_eq!(device.sensors[0].update_count, 1);
+        assert_eq!(device.sensors[0].state(), OcsdSensorState::Ok);
+        assert!(device.sensors[0]
+            .status
+            .contains(OcsdSensorStatus::NotFailed));
+        assert_eq!(device.sensors[1].state(), OcsdSensorState::Failed);
+        assert_eq!(device.sensors[1].valid_reading(), None);
+
+        // recovers once the input reappears
+        fs::write(chip.join("temp3_input"), "61000\n").unwrap();
+        assert!(daemon.tick().unwrap().is_empty());
+        assert_eq!(read_device(&backend, 2).sensors[1].reading.degrees(), 61);
+
+        // above the range of a signed byte
+        fs::write(chip.join("temp1_input"), "200000\n").unwrap();
+        assert!(daemon.tick().unwrap().is_empty());
+        assert_eq!(read_device(&backend, 2).sensors[0].reading.raw_value(), 200);
+    }
+
+    #[test]
+    fn watchdog() {
+        let root = tempfile::tempdir().unwrap();
+        let chip = add_chip(
+            root.path(),
+            "hwmon3",
+            "amdgpu",
+            &[(1, Some("edge"), 45000), (3, None, 60000)],
+        );
+        let backend = simulated_buffer();
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let mut grace = config(root.path(), 2);
+        grace.stale_intervals = DEFAULT_STALE_INTERVALS;
+        let mut daemon = Daemon::new(context, grace).unwrap();
+        assert!(daemon.tick().unwrap().is_empty());
+
+        // the last reading is kept during the grace period
+        fs::remove_file(chip.join("temp3_input")).unwrap();
+        assert_eq!(daemon.tick().unwrap().len(), 1);
+        let device = read_device(&backend, 2);
+        assert_eq!(device.sensors[0].state(), OcsdSensorState::Ok);
+        assert_eq!(
+            device.sensors[1].valid_reading().map(|r| r.degrees()),
+            Some(60)
+        );
+
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let mut fail_safe = config(root.path(), 2);
+        fail_safe.fail_safe_reading = Some(105);
+        let mut daemon = Daemon::new(context, fail_safe).unwrap();
+        assert_eq!(daemon.tick().unwrap().len(), 1);
+        let device = read_device(&backend, 2);
+        assert_eq!(device.sensors[0].reading.degrees(), 45);
+        assert_eq!(
+            device.sensors[1].valid_reading().map(|r| r.degrees()),
+            Some(105)
+        );
+    }
+
+    #[test]
+    fn hanging_source() {
+        let root = tempfile::tempdir().unwrap();
+        let chip = add_chip(
+            root.path(),
+            "hwmon3",
+            "amdgpu",
+            &[(1, Some("edge"), 45000), (3, None, 60000)],
+        );
+        // reads of a FIFO block until it is opened for writing
+        let fifo = chip.join("temp3_input");
+        fs::remove_file(&fifo).unwrap();
+        assert!(std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap()
+            .success());
+
+        let backend = simulated_buffer();
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let mut daemon = Daemon::new(context, config(root.path(), 2)).unwrap();
+        let start = Instant::now();
+        let errors = daemon.tick().unwrap();
+        assert!(start.elapsed() < READ_TIMEOUT * 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error.kind(), io::ErrorKind::TimedOut);
+        let device = read_device(&backend, 2);
+        assert_eq!(device.sensors[0].reading.degrees(), 45);
+        assert_eq!(device.sensors[1].state(), OcsdSensorState::Failed);
+
+        // the hung read is still used once it completes
+        fs::write(&fifo, "61000\n").unwrap();
+        assert!(daemon.tick().unwrap().is_empty());
+        assert_eq!(read_device(&backend, 2).sensors[1].reading.degrees(), 61);
+    }
+
+    #[test]
+    fn shutdown_policy() {
+        let root = tempfile::tempdir().unwrap();
+        add_chip(
+            root.path(),
+            "hwmon3",
+            "amdgpu",
+            &[(1, Some("edge"), 45000), (3, None, 60000)],
+        );
+        let buffers_in_use = |backend: &InMemoryBackend| {
+            OcsdHeader::try_from(&backend.contents()[0..0x40])
+                .unwrap()
+                .buffers_in_use
+        };
+
+        let backend = simulated_buffer();
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let mut restore = config(root.path(), 2);
+        restore.shutdown_policy = ShutdownPolicy::RestoreHeader;
+        let mut daemon = Daemon::new(context, restore).unwrap();
+        daemon.tick().unwrap();
+        assert_eq!(buffers_in_use(&backend), 3);
+        drop(daemon);
+        assert_eq!(buffers_in_use(&backend), 1);
+
+        let backend = simulated_buffer();
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let mut fail = config(root.path(), 2);
+        fail.shutdown_policy = ShutdownPolicy::FailSensors;
+        let mut daemon = Daemon::new(context, fail).unwrap();
+        daemon.tick().unwrap();
+        daemon.shutdown().unwrap();
+        let device = read_device(&backend, 2);
+        assert_eq!(buffers_in_use(&backend), 3);
+        assert!(device.sensors[..2]
+            .iter()
+            .all(|sensor| sensor.state() == OcsdSensorState::Failed));
+    }
+
+    #[test]
+    fn resume_update_count() {
+        let root = tempfile::tempdir().unwrap();
+        add_chip(
+            root.path(),
+            "hwmon0",
+            "amdgpu",
+            &[(1, Some("edge"), 45000), (3, None, 60000)],
+        );
+        let backend = simulated_buffer();
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let mut persistent = config(root.path(), 1);
+        persistent.state_dir = Some(root.path().to_path_buf());
+        let mut daemon = Daemon::new(context, persistent).unwrap();
+        for _ in 0..5 {
+            daemon.tick().unwrap();
+        }
+        drop(daemon);
+        assert_eq!(
+            fs::read_to_string(root.path().join("slot1")).unwrap(),
+            "64\n"
+        );
+
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let mut daemon = Daemon::new(context, config(root.path(), 1)).unwrap();
+        daemon.tick().unwrap();
+        assert_eq!(read_device(&backend, 1).sensors[0].update_count, 5);
+
+        // a corrupt state file is replaced, restarting the count
+        fs::write(root.path().join("slot1"), "").unwrap();
+        let backend = simulated_buffer();
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        let mut persistent = config(root.path(), 1);
+        persistent.state_dir = Some(root.path().to_path_buf());
+        let mut daemon = Daemon::new(context, persistent).unwrap();
+        daemon.tick().unwrap();
+        assert_eq!(read_device(&backend, 1).sensors[0].update_count, 0);
+    }
+
+    #[test]
+    fn invalid_config() {
+        let root = tempfile::tempdir().unwrap();
+        let backend = simulated_buffer();
+
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        assert!(matches!(
+            Daemon::new(context, config(root.path(), 4)),
+            Err(DaemonError::Config(_))
+        ));
+
+        let mut config = config(root.path(), 0);
+        config.devices[0].sensors[1].caution_threshold = 300;
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        assert!(matches!(
+            Daemon::new(context, config),
+            Err(DaemonError::Config(_))
+        ));
+
+        let mut config = self::config(root.path(), 0);
+        config.fail_safe_reading = Some(300);
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        assert!(matches!(
+            Daemon::new(context, config),
+            Err(DaemonError::Config(_))
+        ));
+
+        let mut config = self::config(root.path(), 0);
+        config.devices[0].sensors[1].caution_threshold = 200;
+        config.temperature_encoding = Some(TemperatureEncoding::Signed);
+        let context = OcsdContext::with_backend(&backend, BASE_ADDRESS).unwrap();
+        assert!(matches!(
+            Daemon::new(context, config),
+            Err(DaemonError::Config(_))
+        ));
+    }
+}
@@ -4,8 +4,12 @@
 //! ## Feature flags
 #![doc = document_features::document_features!(feature_label = r#"<span class="stab portability"><code>{feature}</code></span>"#)]
 
-#[cfg(feature = "devmem")]
 pub mod client;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "daemon")]
+pub mod daemon;
 pub mod protocol;
+pub mod sysfs;
 
 pub use protocol::*;
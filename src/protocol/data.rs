@@ -1,91 +1,152 @@
+//! Raw OCSD structures, as laid out in memory.
+//!
+//! All multi-byte values are stored little-endian regardless of the host's
+//! byte order, using [Le16] and [Le32].
+
+/// A `u16` stored in little-endian byte order.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub(super) struct Le16([u8; 2]);
+
+impl Le16 {
+    pub const fn new(value: u16) -> Self {
+        Self(value.to_le_bytes())
+    }
+
+    pub const fn get(self) -> u16 {
+        u16::from_le_bytes(self.0)
+    }
+}
+
+/// A `u32` stored in little-endian byte order.
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub(super) struct Le32([u8; 4]);
+
+impl Le32 {
+    pub const fn new(value: u32) -> Self {
+        Self(value.to_le_bytes())
+    }
+
+    pub const fn get(self) -> u32 {
+        u32::from_le_bytes(self.0)
+    }
+}
+
 #[derive(bytemuck::NoUninit, bytemuck::AnyBitPattern, Clone, Copy, Default)]
 #[repr(C)]
 pub(super) struct OcsdHeaderData {
-    // all values little-endian
     pub ocsd_version: u8,
     _ocsd_version_padding: [u8; 3],
-    pub buffer_size: u16,
+    pub buffer_size: Le16,
     _buffer_size_padding: [u8; 2],
     pub max_option_cards: u8,
     _max_option_cards_padding: [u8; 3],
     pub one_option_card_size: u8,
     _one_option_card_size_padding: [u8; 3],
-    pub buffer_start_address: u32,
-    _padding_0: [u32; 3],
+    pub buffer_start_address: Le32,
+    pub padding_0: [Le32; 3],
     pub update_interval: u8,
     pub _update_interval_padding: [u8; 3],
-    _padding_1: [u32; 5],
+    pub padding_1: [Le32; 5],
     pub buffers_in_use: u8,
     pub _buffers_in_use_padding: [u8; 3],
-    checksum: u32,
+    checksum: Le32,
 }
 
 impl OcsdHeaderData {
     /// Constructs an OCSD system header.
     /// Checksum is automatically calculated.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         ocsd_version: u8,
         buffer_size: u16,
         max_option_cards: u8,
         one_option_card_size: u8,
         buffer_start_address: u32,
+        padding_0: [u32; 3],
         update_interval: u8,
+        padding_1: [u32; 5],
         buffers_in_use: u8,
     ) -> Self {
         let mut constructed = Self {
             ocsd_version,
-            buffer_size,
+            buffer_size: Le16::new(buffer_size),
             max_option_cards,
             one_option_card_size,
-            buffer_start_address,
+            buffer_start_address: Le32::new(buffer_start_address),
+            padding_0: padding_0.map(Le32::new),
             update_interval,
+            padding_1: padding_1.map(Le32::new),
             buffers_in_use,
             ..Default::default()
         };
-        constructed.checksum = constructed.checksum();
+        constructed.update_checksum();
         constructed
     }
 
+    /// Byte offset of the buffers in use field, which is followed only by
+    /// its padding and the checksum.
+    pub const BUFFERS_IN_USE_OFFSET: usize = std::mem::offset_of!(Self, buffers_in_use);
+
     pub fn checksum(&self) -> u32 {
         u32::wrapping_sub(0x00, self.ocsd_version.into())
-            .wrapping_sub(self.buffer_size.into())
+            .wrapping_sub(self.buffer_size.get().into())
             .wrapping_sub(self.max_option_cards.into())
             .wrapping_sub(self.one_option_card_size.into())
-            .wrapping_sub(self.buffer_start_address)
+            .wrapping_sub(self.buffer_start_address.get())
             .wrapping_sub(self.update_interval.into())
             .wrapping_sub(self.buffers_in_use.into())
     }
+
+    pub fn stored_checksum(&self) -> u32 {
+        self.checksum.get()
+    }
+
+    pub fn update_checksum(&mut self) {
+        self.checksum = Le32::new(self.checksum());
+    }
 }
 
 #[derive(bytemuck::NoUninit, bytemuck::AnyBitPattern, Clone, Copy, Default)]
 #[repr(C)]
 pub(super) struct OcsdDeviceHeaderData {
-    // all values little-endian
     pub version: u8,
     _version_padding: [u8; 3],
     pub pci_bus: u8,
     _pci_bus_padding: [u8; 3],
     pub pci_device: u8,
     _pci_device_padding: [u8; 3],
-    _unknown_1: u32,
-    _unknown_2: u32,
-    pub flags_caps: u32,
-    _unknown_3: [u32; 9],
-    checksum: u32,
+    pub unknown_1: Le32,
+    pub unknown_2: Le32,
+    pub flags_caps: Le32,
+    pub unknown_3: [Le32; 9],
+    checksum: Le32,
 }
 
 impl OcsdDeviceHeaderData {
     /// Constructs an OCSD device header.
     /// Checksum is automatically calculated.
-    pub fn new(version: u8, pci_bus: u8, pci_device: u8, flags_caps: u32) -> Self {
+    pub fn new(
+        version: u8,
+        pci_bus: u8,
+        pci_device: u8,
+        unknown_1: u32,
+        unknown_2: u32,
+        flags_caps: u32,
+        unknown_3: [u32; 9],
+    ) -> Self {
         let mut created = Self {
             version,
             pci_bus,
             pci_device,
-            flags_caps,
+            unknown_1: Le32::new(unknown_1),
+            unknown_2: Le32::new(unknown_2),
+            flags_caps: Le32::new(flags_caps),
+            unknown_3: unknown_3.map(Le32::new),
             ..Default::default()
         };
-        created.checksum = created.checksum();
+        created.checksum = Le32::new(created.checksum());
         created
     }
 
@@ -93,53 +154,47 @@ impl OcsdDeviceHeaderData {
         u32::wrapping_sub(0x0, self.version.into())
             .wrapping_sub(self.pci_bus.into())
             .wrapping_sub(self.pci_device.into())
-            .wrapping_sub(self._unknown_1)
-            .wrapping_sub(self._unknown_2)
-            .wrapping_sub(self.flags_caps)
-            .wrapping_sub(self._unknown_3[0])
-            .wrapping_sub(self._unknown_3[1])
-            .wrapping_sub(self._unknown_3[2])
-            .wrapping_sub(self._unknown_3[3])
-            .wrapping_sub(self._unknown_3[4])
-            .wrapping_sub(self._unknown_3[5])
-            .wrapping_sub(self._unknown_3[6])
-            .wrapping_sub(self._unknown_3[7])
-            .wrapping_sub(self._unknown_3[8])
+            .wrapping_sub(self.unknown_1.get())
+            .wrapping_sub(self.unknown_2.get())
+            .wrapping_sub(self.flags_caps.get())
+            .wrapping_sub(self.unknown_3[0].get())
+            .wrapping_sub(self.unknown_3[1].get())
+            .wrapping_sub(self.unknown_3[2].get())
+            .wrapping_sub(self.unknown_3[3].get())
+            .wrapping_sub(self.unknown_3[4].get())
+            .wrapping_sub(self.unknown_3[5].get())
+            .wrapping_sub(self.unknown_3[6].get())
+            .wrapping_sub(self.unknown_3[7].get())
+            .wrapping_sub(self.unknown_3[8].get())
     }
-}
 
-#[derive(bytemuck::NoUninit, bytemuck::AnyBitPattern, Clone, Copy, Default)]
-#[repr(C)]
-pub(super) struct OcsdDeviceData {
-    // all values little-endian
-    pub header: OcsdDeviceHeaderData,
-    pub sensor_0: OcsdSensorData,
-    pub sensor_1: OcsdSensorData,
-    pub sensor_2: OcsdSensorData,
+    pub fn stored_checksum(&self) -> u32 {
+        self.checksum.get()
+    }
 }
 
 #[derive(bytemuck::NoUninit, bytemuck::AnyBitPattern, Clone, Copy, Default)]
 #[repr(C)]
 pub(super) struct OcsdSensorData {
-    // all values little-endian
     pub sensor_type: u8,
     _sensor_type_padding: [u8; 3],
-    pub sensor_location: u32,
+    pub sensor_location: Le32,
     pub caution_threshold: u8, // degrees C
     _caution_threshold_padding: [u8; 3],
     pub max_continuous_threshold: u8, // degrees C
     _max_continuous_threshold_padding: [u8; 3],
-    pub configuration_status: u32, // bytes 0-1: configuration, bytes 2-3: status
-    pub reading: u8,               // degrees C
+    pub configuration_status: Le32, // bytes 0-1: configuration, bytes 2-3: status
+    pub reading: u8,                // degrees C
     _reading_padding: [u8; 3],
-    pub update_count: u16,
+    pub update_count: Le16,
     _update_count_padding: [u8; 2],
-    checksum: u32,
+    checksum: Le32,
 }
 
 impl OcsdSensorData {
     /// Constructs a single OCSD sensor data.
     /// Checksum is automatically calculated.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         sensor_type: u8,
         sensor_location: u32,
@@ -152,41 +207,51 @@ impl OcsdSensorData {
         bus: u8,
     ) -> Self {
         let mut created = Self {
-            sensor_type: sensor_type,
-            sensor_location: sensor_location,
+            sensor_type,
+            sensor_location: Le32::new(sensor_location),
             max_continuous_threshold,
             caution_threshold,
-            configuration_status: (configuration as u32) + ((status as u32) << 16),
+            configuration_status: Le32::new((configuration as u32) + ((status as u32) << 16)),
             reading,
-            update_count,
+            update_count: Le16::new(update_count),
             ..Default::default()
         };
-        created.checksum = created.checksum(bus);
+        created.checksum = Le32::new(created.checksum(bus));
         created
     }
 
     pub fn status(&self) -> u16 {
-        (self.configuration_status >> 16).try_into().unwrap()
+        (self.configuration_status.get() >> 16).try_into().unwrap()
     }
 
     pub fn configuration(&self) -> u16 {
-        (self.configuration_status & 0xFFFF).try_into().unwrap()
+        (self.configuration_status.get() & 0xFFFF)
+            .try_into()
+            .unwrap()
     }
 
     pub fn checksum(&self, bus: u8) -> u32 {
-        let sum = self.sensor_type as u32
-            + self.sensor_location as u32
-            + self.max_continuous_threshold as u32
-            + self.caution_threshold as u32
-            + self.configuration_status as u32
-            + self.reading as u32
-            + self.update_count as u32;
+        let sum = u32::wrapping_add(self.sensor_type.into(), self.sensor_location.get())
+            .wrapping_add(self.max_continuous_threshold.into())
+            .wrapping_add(self.caution_threshold.into())
+            .wrapping_add(self.configuration_status.get())
+            .wrapping_add(self.reading.into())
+            .wrapping_add(self.update_count.get().into());
         if sum == 0 {
             0x00
         } else {
-            u32::wrapping_sub(0x0, sum + bus as u32)
+            u32::wrapping_sub(0x0, sum.wrapping_add(bus.into()))
         }
     }
+
+    pub fn stored_checksum(&self) -> u32 {
+        self.checksum.get()
+    }
+
+    /// True for an unused sensor slot, which is stored as all zeroes.
+    pub fn is_null(&self) -> bool {
+        bytemuck::bytes_of(self).iter().all(|b| *b == 0)
+    }
 }
 
 #[cfg(test)]
@@ -201,7 +266,7 @@ mod tests {
             0x00, 0x00, 0x00, 0x00,
         ];
         let sensor: OcsdSensorData = *bytemuck::from_bytes(&sensor_data);
-        assert_eq!(sensor.checksum(0x03), sensor.checksum);
+        assert_eq!(sensor.checksum(0x03), sensor.stored_checksum());
     }
 
     #[test]
@@ -212,12 +277,12 @@ mod tests {
             0x91, 0x23, 0xf4, 0xff,
         ];
         let sensor: OcsdSensorData = *bytemuck::from_bytes(&sensor_data);
-        assert_eq!(sensor.checksum(0x03), sensor.checksum);
+        assert_eq!(sensor.checksum(0x03), sensor.stored_checksum());
 
-        let new_sensor = sensor.clone();
+        let new_sensor = sensor;
         assert_eq!(
             bytemuck::bytes_of(&OcsdSensorData {
-                checksum: new_sensor.checksum(0x03),
+                checksum: Le32::new(new_sensor.checksum(0x03)),
                 ..new_sensor
             }),
             sensor_data
@@ -232,6 +297,6 @@ mod tests {
             0xcb, 0x23, 0xf4, 0xff,
         ];
         let sensor: OcsdSensorData = *bytemuck::from_bytes(&sensor_data);
-        assert_eq!(sensor.checksum(0x03), sensor.checksum);
+        assert_eq!(sensor.checksum(0x03), sensor.stored_checksum());
     }
 }
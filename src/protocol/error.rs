@@ -1,3 +1,135 @@
 //! OCSD protocol error types.
 
-pub use super::temperature::TempOutOfRange;
+use std::{error::Error, fmt::Display};
+
+pub use super::temperature::{InvalidEncoding, TempOutOfRange};
+
+/// Identifies one of the structures making up the OCSD buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OcsdStructure {
+    /// The OCSD system header
+    Header,
+    /// A single OCSD device, including its header and sensors
+    Device,
+    /// The header of a single OCSD device
+    DeviceHeader,
+    /// A single sensor. When decoded as part of a device, this holds the
+    /// sensor's index within that device.
+    Sensor(Option<usize>),
+    /// A binary image of the header and all device slots
+    Snapshot,
+}
+
+impl Display for OcsdStructure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Header => write!(f, "ocsd header"),
+            Self::Device => write!(f, "device"),
+            Self::DeviceHeader => write!(f, "device header"),
+            Self::Sensor(Some(index)) => write!(f, "sensor {}", index),
+            Self::Sensor(None) => write!(f, "sensor"),
+            Self::Snapshot => write!(f, "snapshot image"),
+        }
+    }
+}
+
+/// Error used when the checksum stored in an OCSD structure does not match
+/// the checksum calculated from its contents, e.g. due to a torn read or
+/// corrupt buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    /// The structure which failed validation
+    pub structure: OcsdStructure,
+    /// Checksum calculated from the structure's contents
+    pub expected: u32,
+    /// Checksum stored in the structure
+    pub found: u32,
+}
+
+impl Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "checksum mismatch in {}: expected {:#010x}, found {:#010x}",
+            self.structure, self.expected, self.found
+        )
+    }
+}
+
+impl Error for ChecksumMismatch {}
+
+/// Error used when an OCSD structure cannot be decoded from a byte buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The buffer is shorter than the structure's memory representation
+    ShortBuffer {
+        /// The structure being decoded
+        structure: OcsdStructure,
+        /// Length of the structure in bytes
+        expected: usize,
+        /// Length of the provided buffer
+        actual: usize,
+    },
+    /// The buffer is longer than the structure's memory representation
+    OversizedBuffer {
+        /// The structure being decoded
+        structure: OcsdStructure,
+        /// Length of the structure in bytes
+        expected: usize,
+        /// Length of the provided buffer
+        actual: usize,
+    },
+    /// The structure has a version identifier which is not supported
+    UnknownVersion {
+        /// The structure being decoded
+        structure: OcsdStructure,
+        /// Raw version identifier
+        version: u8,
+    },
+    /// The stored checksum does not match the structure's contents
+    BadChecksum(ChecksumMismatch),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ShortBuffer {
+                structure,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "buffer too short for {}: expected {} bytes, got {}",
+                structure, expected, actual
+            ),
+            Self::OversizedBuffer {
+                structure,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "buffer too long for {}: expected {} bytes, got {}",
+                structure, expected, actual
+            ),
+            Self::UnknownVersion { structure, version } => {
+                write!(f, "unknown {} version {}", structure, version)
+            }
+            Self::BadChecksum(mismatch) => mismatch.fmt(f),
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::BadChecksum(mismatch) => Some(mismatch),
+            _ => None,
+        }
+    }
+}
+
+impl From<ChecksumMismatch> for DecodeError {
+    fn from(value: ChecksumMismatch) -> Self {
+        Self::BadChecksum(value)
+    }
+}
@@ -7,4 +7,4 @@ mod ocsd;
 mod temperature;
 
 pub use ocsd::*;
-pub use temperature::Celsius;
+pub use temperature::{Celsius, Rounding, TemperatureEncoding};
@@ -1,7 +1,13 @@
-use std::mem::size_of;
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    mem::size_of,
+    ops::BitOr,
+};
 
 use super::{
-    data::{OcsdDeviceHeaderData, OcsdHeaderData, OcsdSensorData},
+    data::{Le32, OcsdDeviceHeaderData, OcsdHeaderData, OcsdSensorData},
+    error::{ChecksumMismatch, DecodeError, OcsdStructure},
     temperature::Celsius,
 };
 
@@ -26,10 +32,377 @@ pub enum OcsdSensorStatus {
     Disabled,
     /// 0b1000: checksum is enabled
     WithChecksum,
+    /// 0b1_0000: the reading is no longer updated. This bit is not used by
+    /// iLO, which sees a stale sensor as failed; see [OcsdSensorState::Stale]
+    Stale,
+}
+
+impl OcsdSensorStatus {
+    const NAMES: [(Self, &'static str); 5] = [
+        (Self::NotFailed, "not_failed"),
+        (Self::Present, "present"),
+        (Self::Disabled, "disabled"),
+        (Self::WithChecksum, "with_checksum"),
+        (Self::Stale, "stale"),
+    ];
+
+    /// Names of the set flags, with bits without a name in hex.
+    fn flag_names(&self) -> impl Iterator<Item = String> + '_ {
+        (0..u16::BITS).filter_map(|bit| {
+            let flag = Self::from(1 << bit);
+            if !self.contains(flag) {
+                return None;
+            }
+            Some(match Self::NAMES.iter().find(|(named, _)| *named == flag) {
+                Some((_, name)) => name.to_string(),
+                None => format!("{:#06x}", flag.bits()),
+            })
+        })
+    }
+}
+
+/// Shows the names of the set flags, e.g. `[not_failed, present]`.
+/// Bits without a name are shown in hex, e.g. `0x0020`.
+impl Display for OcsdSensorStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!(
+            "[{}]",
+            self.flag_names().collect::<Vec<_>>().join(", ")
+        ))
+    }
+}
+
+/// Serialised as a list of flag names, e.g. `["not_failed", "present"]`.
+/// Bits without a name are listed as hex strings, e.g. `"0x0020"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for OcsdSensorStatus {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.flag_names())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OcsdSensorStatus {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .try_fold(Self::none(), |status, name| {
+                let flag = match Self::NAMES.iter().find(|(_, named)| named == name) {
+                    Some((flag, _)) => *flag,
+                    None => name
+                        .strip_prefix("0x")
+                        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+                        .map(Self::from)
+                        .ok_or_else(|| {
+                            serde::de::Error::custom(format!("unknown sensor status {:?}", name))
+                        })?,
+                };
+                Ok(status | flag)
+            })
+    }
+}
+
+/// State of a single OCSD sensor, as encoded in its status flags.
+///
+/// Only the [NotFailed](OcsdSensorStatus::NotFailed),
+/// [Present](OcsdSensorStatus::Present),
+/// [Disabled](OcsdSensorStatus::Disabled) and
+/// [Stale](OcsdSensorStatus::Stale) flags make up the state; other flags are
+/// left as they are. Each state has distinct flags, so it decodes whatever
+/// the reading, which is cleared to a raw value of 0 for sensors without a
+/// valid reading:
+///
+/// | State         | Flags               | Reading |
+/// |---------------|---------------------|---------|
+/// | `ok`          | not_failed, present | current |
+/// | `stale`       | present, stale      | last    |
+/// | `failed`      | present             | 0       |
+/// | `not_present` | none                | 0       |
+/// | `disabled`    | present, disabled   | 0       |
+///
+/// No flag observed from iLO marks a reading as stale, so the stale flag is
+/// a bit iLO doesn't use: iLO sees a stale sensor as failed, while reporters
+/// reading the buffer back can still tell the last known value apart from a
+/// failure.
+///
+/// # Examples
+/// ```
+/// use ocsd::protocol::{Celsius, OcsdSensor, OcsdSensorState};
+///
+/// let mut sensor = OcsdSensor {
+///     reading: Celsius::new(45).unwrap(),
+///     bus: Some(0x04),
+///     ..Default::default()
+/// };
+/// sensor.set_state(OcsdSensorState::Failed);
+/// assert_eq!(sensor.reading.raw_value(), 0);
+/// assert_eq!(sensor.state(), OcsdSensorState::Failed);
+/// assert_eq!(sensor.valid_reading(), None);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OcsdSensorState {
+    /// The reading is current
+    #[default]
+    Ok,
+    /// The reading is no longer updated, and holds the last known value
+    Stale,
+    /// The sensor has failed, and has no reading
+    Failed,
+    /// The sensor is not present
+    NotPresent,
+    /// The sensor is disabled
+    Disabled,
+}
+
+impl OcsdSensorState {
+    const FLAGS: OcsdSensorStatus = OcsdSensorStatus::NotFailed
+        .or(OcsdSensorStatus::Present)
+        .or(OcsdSensorStatus::Disabled)
+        .or(OcsdSensorStatus::Stale);
+
+    /// Status flags encoding this state.
+    pub const fn status(&self) -> OcsdSensorStatus {
+        match self {
+            Self::Ok => OcsdSensorStatus::NotFailed.or(OcsdSensorStatus::Present),
+            Self::Stale => OcsdSensorStatus::Present.or(OcsdSensorStatus::Stale),
+            Self::Failed => OcsdSensorStatus::Present,
+            Self::NotPresent => OcsdSensorStatus::none(),
+            Self::Disabled => OcsdSensorStatus::Present.or(OcsdSensorStatus::Disabled),
+        }
+    }
+
+    /// Returns true if the reading of a sensor in this state is kept when
+    /// encoding.
+    pub const fn has_reading(&self) -> bool {
+        matches!(self, Self::Ok | Self::Stale)
+    }
+}
+
+/// Shows the snake_case name used in serialised output, e.g. `not_present`.
+impl Display for OcsdSensorState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Ok => "ok",
+            Self::Stale => "stale",
+            Self::Failed => "failed",
+            Self::NotPresent => "not_present",
+            Self::Disabled => "disabled",
+        })
+    }
+}
+
+/// u16 bitmask holding a single OCSD sensor's configuration.
+///
+/// The meaning of the configuration bits is not yet understood; all sensors
+/// observed so far have a configuration of 0. Bits are preserved as read and
+/// shown in hex, alongside the names of any bits listed in
+/// [NAMES](OcsdSensorConfiguration::NAMES).
+///
+/// # Examples
+/// ```
+/// use ocsd::protocol::OcsdSensorConfiguration;
+///
+/// let configuration = OcsdSensorConfiguration::from(0x0012);
+/// assert_eq!(configuration.unknown_bits(), 0x0012);
+/// assert_eq!(configuration.to_string(), "0x0012 (unknown 0x0012)");
+/// ```
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct OcsdSensorConfiguration(u16);
+
+impl OcsdSensorConfiguration {
+    /// Names of the configuration bits whose meaning is known.
+    pub const NAMES: &'static [(Self, &'static str)] = &[];
+
+    /// Returns the raw configuration bits.
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+
+    /// Returns true if all bits set in `other` are set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the set bits which have no name.
+    pub fn unknown_bits(&self) -> u16 {
+        Self::NAMES
+            .iter()
+            .fold(self.0, |bits, (named, _)| bits & !named.0)
+    }
+}
+
+impl From<u16> for OcsdSensorConfiguration {
+    fn from(value: u16) -> Self {
+        Self(value)
+    }
+}
+
+impl From<OcsdSensorConfiguration> for u16 {
+    fn from(value: OcsdSensorConfiguration) -> Self {
+        value.0
+    }
+}
+
+/// Shows the raw bits in hex, followed by the names of any known bits and the
+/// remaining unknown bits, e.g. `0x0012 (unknown 0x0012)`.
+impl Display for OcsdSensorConfiguration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_bits(
+            f,
+            self.0.into(),
+            6,
+            Self::NAMES
+                .iter()
+                .map(|(named, name)| (named.0.into(), *name)),
+        )
+    }
+}
+
+impl Debug for OcsdSensorConfiguration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OcsdSensorConfiguration({})", self)
+    }
+}
+
+/// u32 bitmask holding a single OCSD device's flags/caps.
+///
+/// Only bit 0x10 has been observed, and is named
+/// [HOST_REPORTED](OcsdDeviceFlags::HOST_REPORTED). Other bits are preserved
+/// as read and shown in hex.
+///
+/// # Examples
+/// ```
+/// use ocsd::protocol::OcsdDeviceFlags;
+///
+/// let flags = OcsdDeviceFlags::from(0x0000_0110);
+/// assert!(flags.contains(OcsdDeviceFlags::HOST_REPORTED));
+/// assert_eq!(flags.unknown_bits(), 0x0000_0100);
+/// assert_eq!(flags.to_string(), "0x00000110 (host_reported, unknown 0x00000100)");
+/// ```
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct OcsdDeviceFlags(u32);
+
+impl OcsdDeviceFlags {
+    /// 0x10: set in every populated device header observed so far, including
+    /// records written by the host for option cards without OCSD support.
+    /// Its exact meaning is unknown, but iLO accepts devices reported with it.
+    pub const HOST_REPORTED: Self = Self(0x0000_0010);
+
+    /// Names of the flags which have been observed.
+    pub const NAMES: &'static [(Self, &'static str)] = &[(Self::HOST_REPORTED, "host_reported")];
+
+    /// Flags for a device whose sensors are reported by the host, as used by
+    /// [OcsdDeviceHeader::host_reported].
+    pub const fn host_reported() -> Self {
+        Self::HOST_REPORTED
+    }
+
+    /// Returns the raw flags.
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns true if all flags set in `other` are set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the set bits which have no name.
+    pub fn unknown_bits(&self) -> u32 {
+        Self::NAMES
+            .iter()
+            .fold(self.0, |bits, (named, _)| bits & !named.0)
+    }
+}
+
+impl From<u32> for OcsdDeviceFlags {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<OcsdDeviceFlags> for u32 {
+    fn from(value: OcsdDeviceFlags) -> Self {
+        value.0
+    }
+}
+
+impl BitOr for OcsdDeviceFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Shows the raw flags in hex, followed by the names of any known flags and
+/// the remaining unknown bits, e.g. `0x00000010 (host_reported)`.
+impl Display for OcsdDeviceFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_bits(
+            f,
+            self.0,
+            10,
+            Self::NAMES.iter().map(|(named, name)| (named.0, *name)),
+        )
+    }
+}
+
+impl Debug for OcsdDeviceFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OcsdDeviceFlags({})", self)
+    }
+}
+
+/// Formats a bitmask as `{bits:#0width$x}`, followed in parentheses by the
+/// names of the set bits in `names` and any other set bits in hex. The
+/// formatter's own width and alignment apply to the whole text.
+fn fmt_bits<'a>(
+    f: &mut std::fmt::Formatter<'_>,
+    bits: u32,
+    width: usize,
+    names: impl Iterator<Item = (u32, &'a str)>,
+) -> std::fmt::Result {
+    let mut text = format!("{:#0width$x}", bits, width = width);
+    let mut parts = Vec::new();
+    let mut unknown = bits;
+    for (mask, name) in names {
+        if mask != 0 && bits & mask == mask {
+            parts.push(name.to_string());
+            unknown &= !mask;
+        }
+    }
+    if unknown != 0 {
+        parts.push(format!("unknown {:#0width$x}", unknown, width = width));
+    }
+    if !parts.is_empty() {
+        text.push_str(&format!(" ({})", parts.join(", ")));
+    }
+    f.pad(&text)
 }
 
 /// Type of OCSD sensor
-#[derive(Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum OcsdSensorType {
     #[default]
     /// Reserved for decoding null sensors or sensors with an unimplemented type
@@ -47,9 +420,24 @@ impl From<u8> for OcsdSensorType {
     }
 }
 
+/// Shows the snake_case name used in serialised output, e.g. `thermal`.
+impl Display for OcsdSensorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Unknown => "unknown",
+            Self::Thermal => "thermal",
+        })
+    }
+}
+
 /// Location of OCSD sensor on the option card
 #[allow(dead_code)]
-#[derive(Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum OcsdSensorLocation {
     #[default]
     /// Reserved for decoding null sensors or sensors with an unimplemented type
@@ -70,9 +458,26 @@ impl From<u32> for OcsdSensorLocation {
     }
 }
 
+/// Shows the snake_case name used in serialised output, e.g. `internal_to_asic`.
+impl Display for OcsdSensorLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Unknown => "unknown",
+            Self::InternalToAsic => "internal_to_asic",
+            Self::OnboardOther => "onboard_other",
+        })
+    }
+}
+
 /// OCSD protocol version
-#[derive(Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum OcsdVersion {
+    #[default]
     /// Reserved for decoding invalid data or header with an unimplemented version
     Unknown = 0,
     /// OCSD version 2
@@ -88,9 +493,25 @@ impl From<u8> for OcsdVersion {
     }
 }
 
+/// Shows the snake_case name used in serialised output, e.g. `version2`.
+impl Display for OcsdVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Unknown => "unknown",
+            Self::Version2 => "version2",
+        })
+    }
+}
+
 /// OCSD device version
-#[derive(Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum DeviceVersion {
+    #[default]
     /// Reserved for decoding null sensors or sensors with an unimplemented type
     Unknown = 0,
     /// Device version 1
@@ -106,21 +527,88 @@ impl From<u8> for DeviceVersion {
     }
 }
 
+/// Shows the snake_case name used in serialised output, e.g. `version1`.
+impl Display for DeviceVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Unknown => "unknown",
+            Self::Version1 => "version1",
+        })
+    }
+}
+
 /// Used for structs which have a 1:1 representation in OCSD shared memory.
 pub trait MemoryMapped {
     /// Returns byte representation of the structure
     /// as it should appeaer in OCSD memory.
-    fn to_bytes(self: &Self) -> Vec<u8>;
+    fn to_bytes(&self) -> Vec<u8>;
 
     /// Constructs the structure from its OCSD
     /// memory representation.
+    ///
+    /// # Panics
+    /// Panics if `bytes` is shorter than [memory_size](MemoryMapped::memory_size).
+    /// Use the [TryFrom] implementation to decode untrusted input.
     fn from_bytes(bytes: &[u8]) -> Self;
 
-    /// Length of the structure in bytes.
+    /// Length of the structure in bytes. For fixed-size structures this is
+    /// the length of [to_bytes](MemoryMapped::to_bytes); variable-size
+    /// structures document what it covers.
     fn memory_size() -> usize;
 }
 
+fn check_length(
+    bytes: &[u8],
+    expected: usize,
+    structure: OcsdStructure,
+) -> Result<(), DecodeError> {
+    match bytes.len().cmp(&expected) {
+        Ordering::Less => Err(DecodeError::ShortBuffer {
+            structure,
+            expected,
+            actual: bytes.len(),
+        }),
+        Ordering::Greater => Err(DecodeError::OversizedBuffer {
+            structure,
+            expected,
+            actual: bytes.len(),
+        }),
+        Ordering::Equal => Ok(()),
+    }
+}
+
+/// Reads a raw data structure from a buffer of exactly the right length.
+/// The buffer does not need to be aligned.
+fn read_data<T: bytemuck::AnyBitPattern>(
+    bytes: &[u8],
+    structure: OcsdStructure,
+) -> Result<T, DecodeError> {
+    check_length(bytes, size_of::<T>(), structure)?;
+    Ok(bytemuck::pod_read_unaligned(bytes))
+}
+
+fn verify_checksum(
+    structure: OcsdStructure,
+    expected: u32,
+    found: u32,
+) -> Result<(), ChecksumMismatch> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(ChecksumMismatch {
+            structure,
+            expected,
+            found,
+        })
+    }
+}
+
 /// Plain representation of OCSD header.
+///
+/// Reserved words are kept as read, so that a header can be read, modified and
+/// written back without losing them.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OcsdHeader {
     /// OCSD system version
     pub ocsd_version: OcsdVersion,
@@ -132,8 +620,14 @@ pub struct OcsdHeader {
     pub one_option_card_size: u8,
     /// System memory address at which the OCSD devices buffer begins
     pub buffer_start_address: u32,
+    /// Reserved words following the buffer start address
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub padding_0: [u32; 3],
     /// Interval at which the devices buffer is polled
     pub update_interval: u8,
+    /// Reserved words following the update interval
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub padding_1: [u32; 5],
     /// Number of devices to be used. This always starts from device 0
     pub buffers_in_use: u8,
 }
@@ -146,41 +640,134 @@ impl MemoryMapped for OcsdHeader {
             self.max_option_cards,
             self.one_option_card_size,
             self.buffer_start_address,
+            self.padding_0,
             self.update_interval,
+            self.padding_1,
             self.buffers_in_use,
         );
         bytemuck::bytes_of(&data).to_vec()
     }
 
     fn from_bytes(bytes: &[u8]) -> Self {
-        let data: OcsdHeaderData = *bytemuck::from_bytes(&bytes);
+        Self::from_data(&bytemuck::pod_read_unaligned(
+            &bytes[0..Self::memory_size()],
+        ))
+    }
+
+    fn memory_size() -> usize {
+        size_of::<OcsdHeaderData>()
+    }
+}
+
+impl OcsdHeader {
+    /// Number of sensors in each device record, as determined by
+    /// [one_option_card_size](OcsdHeader::one_option_card_size).
+    pub fn sensors_per_device(&self) -> usize {
+        OcsdDevice::sensor_capacity(self.one_option_card_size as usize)
+    }
+
+    /// Constructs the header from its OCSD memory representation,
+    /// returning an error if the buffer is the wrong length or the
+    /// stored checksum does not match its contents.
+    pub fn from_bytes_checked(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Self::decode(bytes, false)
+    }
+
+    /// Encodes the change of [buffers_in_use](OcsdHeader::buffers_in_use) in
+    /// the raw header `bytes`, returning the offset and contents of the bytes
+    /// to write: the field itself, its padding and the checksum. All other
+    /// bytes are left as they are.
+    pub(crate) fn buffers_in_use_patch(bytes: &[u8], buffers_in_use: u8) -> (usize, Vec<u8>) {
+        let mut data: OcsdHeaderData = bytemuck::pod_read_unaligned(&bytes[0..Self::memory_size()]);
+        data.buffers_in_use = buffers_in_use;
+        data.update_checksum();
+        let offset = OcsdHeaderData::BUFFERS_IN_USE_OFFSET;
+        (offset, bytemuck::bytes_of(&data)[offset..].to_vec())
+    }
+
+    fn decode(bytes: &[u8], check_version: bool) -> Result<Self, DecodeError> {
+        let data: OcsdHeaderData = read_data(bytes, OcsdStructure::Header)?;
+        if check_version {
+            if let OcsdVersion::Unknown = data.ocsd_version.into() {
+                return Err(DecodeError::UnknownVersion {
+                    structure: OcsdStructure::Header,
+                    version: data.ocsd_version,
+                });
+            }
+        }
+        verify_checksum(
+            OcsdStructure::Header,
+            data.checksum(),
+            data.stored_checksum(),
+        )?;
+        Ok(Self::from_data(&data))
+    }
+
+    fn from_data(data: &OcsdHeaderData) -> Self {
         Self {
             ocsd_version: data.ocsd_version.into(),
-            buffer_size: data.buffer_size,
+            buffer_size: data.buffer_size.get(),
             max_option_cards: data.max_option_cards,
             one_option_card_size: data.one_option_card_size,
-            buffer_start_address: data.buffer_start_address,
+            buffer_start_address: data.buffer_start_address.get(),
+            padding_0: data.padding_0.map(Le32::get),
             update_interval: data.update_interval,
+            padding_1: data.padding_1.map(Le32::get),
             buffers_in_use: data.buffers_in_use,
         }
     }
+}
 
-    fn memory_size() -> usize {
-        size_of::<OcsdHeaderData>()
+/// Decodes the header, validating its length, version and checksum.
+impl TryFrom<&[u8]> for OcsdHeader {
+    type Error = DecodeError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(bytes, true)
     }
 }
 
+/// Shows the header on one line, e.g. `version2, 8 option cards of 0xa0 bytes
+/// at 0x791f6040, 2 in use, update interval 1`.
+impl Display for OcsdHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {} option cards of {:#x} bytes at {:#x}, {} in use, update interval {}",
+            self.ocsd_version,
+            self.max_option_cards,
+            self.one_option_card_size,
+            self.buffer_start_address,
+            self.buffers_in_use,
+            self.update_interval
+        )
+    }
+}
+
+/// Number of sensors in each device on the ML350 Gen9, whose option card
+/// size is 0xa0 bytes.
+pub const DEFAULT_SENSOR_COUNT: usize = 3;
+
 /// Plain struct representing a single OCSD device.
-/// This implementation assumes fixed-size devices with
-/// 3 sensor slots.
+///
+/// The number of sensors in a device record is determined by the header's
+/// [one_option_card_size](OcsdHeader::one_option_card_size); see
+/// [OcsdHeader::sensors_per_device].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OcsdDevice {
     /// Associates the OCSD device with a PCI device; also provides some extra information
     pub header: OcsdDeviceHeader,
-    /// Each device can have up to 3 sensors. Unused sensors should be set to Default::default.
-    pub sensors: [OcsdSensor; 3],
+    /// Sensors of the device. Unused sensors should be set to Default::default,
+    /// and sensors missing from the end of the record are written as null sensors.
+    pub sensors: Vec<OcsdSensor>,
 }
 
 impl MemoryMapped for OcsdDevice {
+    /// Writes the header followed by every sensor, so the result is
+    /// [record_size](OcsdDevice::record_size) bytes long, which is only
+    /// [memory_size](OcsdDevice::memory_size) for [DEFAULT_SENSOR_COUNT] sensors.
+    /// Nothing is padded; callers writing into a larger slot pad it themselves.
     fn to_bytes(&self) -> Vec<u8> {
         let mut some_bytes = self.header.to_bytes();
         let mut sensors_bytes: Vec<u8> = self
@@ -192,24 +779,144 @@ impl MemoryMapped for OcsdDevice {
         some_bytes
     }
 
+    /// Constructs the device from its OCSD memory representation, with as many
+    /// sensors as fit in `bytes`.
+    ///
+    /// As with [from_bytes_checked](OcsdDevice::from_bytes_checked), non-null
+    /// sensors are given the device header's PCI bus, so that writing the
+    /// device back doesn't replace them with null sensors.
     fn from_bytes(bytes: &[u8]) -> Self {
         let header = OcsdDeviceHeader::from_bytes(&bytes[0..OcsdDeviceHeader::memory_size()]);
-        let sensor_bytes = &bytes[OcsdDeviceHeader::memory_size()..];
-        let mut sensors: [OcsdSensor; 3] = Default::default();
-        for i in 0..3 {
-            sensors[i] = OcsdSensor::from_bytes(
-                &sensor_bytes[i * OcsdSensor::memory_size()..(i + 1) * OcsdSensor::memory_size()],
-            );
-        }
+        let sensors = bytes[OcsdDeviceHeader::memory_size()..]
+            .chunks_exact(OcsdSensor::memory_size())
+            .map(|sensor_bytes| {
+                let data: OcsdSensorData = bytemuck::pod_read_unaligned(sensor_bytes);
+                let bus = (!data.is_null()).then_some(header.pci_bus);
+                OcsdSensor::from_data(&data, bus)
+            })
+            .collect();
         Self { header, sensors }
     }
 
+    /// Length of a device with [DEFAULT_SENSOR_COUNT] sensors. This is not the
+    /// length of [to_bytes](OcsdDevice::to_bytes) for other sensor counts; use
+    /// [record_size](OcsdDevice::record_size) for the length of a particular device.
     fn memory_size() -> usize {
-        OcsdDeviceHeader::memory_size() + 3 * OcsdSensor::memory_size()
+        Self::size_with_sensors(DEFAULT_SENSOR_COUNT)
+    }
+}
+
+impl OcsdDevice {
+    /// Number of sensors which fit in a device record of `record_size` bytes.
+    pub fn sensor_capacity(record_size: usize) -> usize {
+        record_size.saturating_sub(OcsdDeviceHeader::memory_size()) / OcsdSensor::memory_size()
+    }
+
+    /// Length of a device record with `sensor_count` sensors.
+    pub fn size_with_sensors(sensor_count: usize) -> usize {
+        OcsdDeviceHeader::memory_size() + sensor_count * OcsdSensor::memory_size()
+    }
+
+    /// Length of this device's memory representation.
+    pub fn record_size(&self) -> usize {
+        Self::size_with_sensors(self.sensors.len())
+    }
+
+    /// Constructs the device from its OCSD memory representation,
+    /// returning an error if the buffer is shorter than the device header,
+    /// or the stored checksum of the header or any sensor does not match its
+    /// contents.
+    ///
+    /// The device has as many sensors as fit in `bytes`; trailing bytes too
+    /// short to hold another sensor are ignored, so a whole slot can be
+    /// passed in. Sensors are validated against the device header's PCI bus.
+    pub fn from_bytes_checked(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Self::decode(bytes, false)
+    }
+
+    fn decode(bytes: &[u8], check_version: bool) -> Result<Self, DecodeError> {
+        if bytes.len() < OcsdDeviceHeader::memory_size() {
+            check_length(bytes, Self::size_with_sensors(0), OcsdStructure::Device)?;
+        }
+        let bytes = &bytes[..Self::size_with_sensors(Self::sensor_capacity(bytes.len()))];
+        let header =
+            OcsdDeviceHeader::decode(&bytes[0..OcsdDeviceHeader::memory_size()], check_version)?;
+        let sensors = bytes[OcsdDeviceHeader::memory_size()..]
+            .chunks_exact(OcsdSensor::memory_size())
+            .enumerate()
+            .map(|(i, sensor_bytes)| {
+                OcsdSensor::decode(sensor_bytes, header.pci_bus, OcsdStructure::Sensor(Some(i)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { header, sensors })
+    }
+}
+
+/// Decodes the device, validating its length, header version and the
+/// checksums of the header and all sensors. As with
+/// [from_bytes_checked](OcsdDevice::from_bytes_checked), trailing bytes too
+/// short to hold another sensor are ignored.
+impl TryFrom<&[u8]> for OcsdDevice {
+    type Error = DecodeError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(bytes, true)
+    }
+}
+
+/// Shows the device header, followed by a table of its sensors:
+///
+/// ```text
+/// version1, pci 04:00, flags/caps 0x00000010 (host_reported)
+///   #  type     location          reading  caution  max continuous  update count  configuration  status
+///   0  thermal  internal_to_asic     40°C     90°C            80°C             0  0x0000         [not_failed, present, with_checksum]
+///   1  null
+///   2  null
+/// ```
+impl Display for OcsdDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\n  {:<2} {:<8} {:<16} {:>8} {:>8} {:>15} {:>13}  {:<13}  status",
+            self.header,
+            "#",
+            "type",
+            "location",
+            "reading",
+            "caution",
+            "max continuous",
+            "update count",
+            "configuration"
+        )?;
+        for (index, sensor) in self.sensors.iter().enumerate() {
+            if sensor.bus.is_none() {
+                write!(f, "\n  {:<2} null", index)?;
+                continue;
+            }
+            write!(
+                f,
+                "\n  {:<2} {:<8} {:<16} {:>8} {:>8} {:>15} {:>13}  {:<13}  {}",
+                index,
+                sensor.sensor_type,
+                sensor.sensor_location,
+                sensor.reading,
+                sensor.caution_threshold,
+                sensor.max_continuous_threshold,
+                sensor.update_count,
+                sensor.configuration,
+                sensor.status
+            )?;
+        }
+        Ok(())
     }
 }
 
 /// Plain struct representing a single OCSD device's header information.
+///
+/// Words whose meaning is unknown are kept as read, so that a header can be
+/// read, modified and written back without losing them.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OcsdDeviceHeader {
     /// OCSD device/header version identifier
     pub version: DeviceVersion,
@@ -217,8 +924,17 @@ pub struct OcsdDeviceHeader {
     pub pci_bus: u8,
     /// PCI device number on the bus (most commonly 0)
     pub pci_device: u8,
+    /// Unknown word following the PCI device number
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub unknown_1: u32,
+    /// Unknown word preceding the flags/caps
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub unknown_2: u32,
     /// Flags/caps information (this is not currently well understood)
-    pub flags_caps: u32,
+    pub flags_caps: OcsdDeviceFlags,
+    /// Unknown words following the flags/caps
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub unknown_3: [u32; 9],
 }
 
 impl MemoryMapped for OcsdDeviceHeader {
@@ -227,35 +943,110 @@ impl MemoryMapped for OcsdDeviceHeader {
             self.version as u8,
             self.pci_bus,
             self.pci_device,
-            self.flags_caps,
+            self.unknown_1,
+            self.unknown_2,
+            self.flags_caps.bits(),
+            self.unknown_3,
         );
         bytemuck::bytes_of(&data).to_vec()
     }
 
     fn from_bytes(bytes: &[u8]) -> Self {
-        let data: OcsdDeviceHeaderData = *bytemuck::from_bytes(&bytes);
+        Self::from_data(&bytemuck::pod_read_unaligned(
+            &bytes[0..Self::memory_size()],
+        ))
+    }
+
+    fn memory_size() -> usize {
+        size_of::<OcsdDeviceHeaderData>()
+    }
+}
+
+impl OcsdDeviceHeader {
+    /// Header for a device on the provided PCI bus and device number whose
+    /// sensors are reported by the host, as written for option cards without
+    /// OCSD support.
+    pub fn host_reported(pci_bus: u8, pci_device: u8) -> Self {
+        Self {
+            version: DeviceVersion::Version1,
+            pci_bus,
+            pci_device,
+            flags_caps: OcsdDeviceFlags::host_reported(),
+            ..Default::default()
+        }
+    }
+
+    /// Constructs the device header from its OCSD memory representation,
+    /// returning an error if the buffer is the wrong length or the
+    /// stored checksum does not match its contents.
+    pub fn from_bytes_checked(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Self::decode(bytes, false)
+    }
+
+    fn decode(bytes: &[u8], check_version: bool) -> Result<Self, DecodeError> {
+        let data: OcsdDeviceHeaderData = read_data(bytes, OcsdStructure::DeviceHeader)?;
+        // version 0 is used by unpopulated device slots
+        if check_version && data.version != 0 {
+            if let DeviceVersion::Unknown = data.version.into() {
+                return Err(DecodeError::UnknownVersion {
+                    structure: OcsdStructure::DeviceHeader,
+                    version: data.version,
+                });
+            }
+        }
+        verify_checksum(
+            OcsdStructure::DeviceHeader,
+            data.checksum(),
+            data.stored_checksum(),
+        )?;
+        Ok(Self::from_data(&data))
+    }
+
+    fn from_data(data: &OcsdDeviceHeaderData) -> Self {
         Self {
             version: data.version.into(),
             pci_bus: data.pci_bus,
             pci_device: data.pci_device,
-            flags_caps: data.flags_caps,
+            unknown_1: data.unknown_1.get(),
+            unknown_2: data.unknown_2.get(),
+            flags_caps: data.flags_caps.get().into(),
+            unknown_3: data.unknown_3.map(Le32::get),
         }
     }
+}
 
-    fn memory_size() -> usize {
-        size_of::<OcsdDeviceHeaderData>()
+/// Decodes the device header, validating its length, version and checksum.
+/// A version of 0 is accepted, as found in unpopulated device slots.
+impl TryFrom<&[u8]> for OcsdDeviceHeader {
+    type Error = DecodeError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(bytes, true)
+    }
+}
+
+/// Shows the header on one line, e.g. `version1, pci 04:00, flags/caps
+/// 0x00000010 (host_reported)`.
+impl Display for OcsdDeviceHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, pci {:02x}:{:02x}, flags/caps {}",
+            self.version, self.pci_bus, self.pci_device, self.flags_caps
+        )
     }
 }
 
 /// Plain struct representing a single sensor reading on a single OCSD device.
-#[derive(Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OcsdSensor {
     /// Type of sensor
     pub sensor_type: OcsdSensorType,
     /// Sensor location on the board/card
     pub sensor_location: OcsdSensorLocation,
     /// Configuration data (not currently well understood)
-    pub configuration: u16,
+    pub configuration: OcsdSensorConfiguration,
     /// Sensor status (TBC, but this seems to be able to be written
     /// either from the device or from iLO)
     pub status: OcsdSensorStatus,
@@ -280,7 +1071,7 @@ pub struct OcsdSensor {
 
 impl MemoryMapped for OcsdSensor {
     /// OCSD buffer compatible representation of sensor data.
-    /// self.bus must be set, or this will return zeroes.
+    /// If self.bus is [None], this returns a null sensor (all zeroes).
     fn to_bytes(&self) -> Vec<u8> {
         match self.bus {
             Some(bus) => {
@@ -290,33 +1081,824 @@ impl MemoryMapped for OcsdSensor {
                     self.caution_threshold.raw_value(),
                     self.max_continuous_threshold.raw_value(),
                     self.reading.raw_value(),
-                    self.configuration,
+                    self.configuration.bits(),
                     self.status.into(),
                     self.update_count,
                     bus,
                 );
                 bytemuck::bytes_of(&data).to_vec()
             }
-            None => Default::default(),
+            None => vec![0x00; Self::memory_size()],
         }
     }
 
     fn from_bytes(bytes: &[u8]) -> Self {
-        let data: OcsdSensorData = *bytemuck::from_bytes(&bytes);
+        Self::from_data(
+            &bytemuck::pod_read_unaligned(&bytes[0..Self::memory_size()]),
+            None,
+        )
+    }
+
+    fn memory_size() -> usize {
+        size_of::<OcsdSensorData>()
+    }
+}
+
+impl OcsdSensor {
+    /// Constructs the sensor from its OCSD memory representation,
+    /// returning an error if the buffer is the wrong length or the
+    /// stored checksum does not match its contents.
+    ///
+    /// The sensor checksum includes the PCI bus of the device it belongs to,
+    /// so this must be provided. The bus is retained in the returned sensor,
+    /// unless the sensor is null.
+    pub fn from_bytes_checked(bytes: &[u8], bus: u8) -> Result<Self, DecodeError> {
+        Self::decode(bytes, bus, OcsdStructure::Sensor(None))
+    }
+
+    /// Decodes the sensor's [state](OcsdSensorState) from its status flags.
+    pub fn state(&self) -> OcsdSensorState {
+        if !self.status.contains(OcsdSensorStatus::Present) {
+            OcsdSensorState::NotPresent
+        } else if self.status.contains(OcsdSensorStatus::Disabled) {
+            OcsdSensorState::Disabled
+        } else if self.status.contains(OcsdSensorStatus::NotFailed) {
+            OcsdSensorState::Ok
+        } else if self.status.contains(OcsdSensorStatus::Stale) {
+            OcsdSensorState::Stale
+        } else {
+            OcsdSensorState::Failed
+        }
+    }
+
+    /// Sets the status flags making up the sensor's [state](OcsdSensorState),
+    /// leaving other flags as they are, and clears the reading if the state
+    /// has none.
+    pub fn set_state(&mut self, state: OcsdSensorState) {
+        self.status = self
+            .status
+            .and(OcsdSensorState::FLAGS.not())
+            .or(state.status());
+        if !state.has_reading() {
+            self.reading = Celsius::default();
+        }
+    }
+
+    /// Returns the reading if it is current, i.e. the sensor is
+    /// [ok](OcsdSensorState::Ok).
+    pub fn valid_reading(&self) -> Option<Celsius> {
+        match self.state() {
+            OcsdSensorState::Ok => Some(self.reading),
+            _ => None,
+        }
+    }
+
+    fn decode(bytes: &[u8], bus: u8, structure: OcsdStructure) -> Result<Self, DecodeError> {
+        let data: OcsdSensorData = read_data(bytes, structure)?;
+        verify_checksum(structure, data.checksum(bus), data.stored_checksum())?;
+        let bus = if data.is_null() { None } else { Some(bus) };
+        Ok(Self::from_data(&data, bus))
+    }
+
+    fn from_data(data: &OcsdSensorData, bus: Option<u8>) -> Self {
         Self {
             sensor_type: data.sensor_type.into(),
-            sensor_location: data.sensor_location.into(),
-            configuration: data.configuration(),
+            sensor_location: data.sensor_location.get().into(),
+            configuration: data.configuration().into(),
             status: data.status().into(),
             max_continuous_threshold: Celsius::from_raw(data.max_continuous_threshold),
             caution_threshold: Celsius::from_raw(data.caution_threshold),
             reading: Celsius::from_raw(data.reading),
-            update_count: data.update_count,
-            bus: None,
+            update_count: data.update_count.get(),
+            bus,
         }
     }
+}
 
-    fn memory_size() -> usize {
-        size_of::<OcsdSensorData>()
+/// Decodes the sensor, validating its length only.
+///
+/// The sensor checksum depends on the PCI bus of the device it belongs to,
+/// which is not part of the sensor's memory representation. Use
+/// [OcsdSensor::from_bytes_checked] or decode the whole [OcsdDevice] to
+/// validate the checksum.
+impl TryFrom<&[u8]> for OcsdSensor {
+    type Error = DecodeError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let data: OcsdSensorData = read_data(bytes, OcsdStructure::Sensor(None))?;
+        Ok(Self::from_data(&data, None))
+    }
+}
+
+/// Shows the sensor on one line, e.g. `thermal internal_to_asic, reading 40°C
+/// (caution 90°C, max continuous 80°C), configuration 0x0000, status
+/// [not_failed, present, with_checksum], update count 0, bus 04`, or `null`
+/// for a null sensor.
+impl Display for OcsdSensor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(bus) = self.bus else {
+            return f.write_str("null");
+        };
+        write!(
+            f,
+            "{} {}, reading {} (caution {}, max continuous {}), configuration {}, \
+             status {}, update count {}, bus {:02x}",
+            self.sensor_type,
+            self.sensor_location,
+            self.reading,
+            self.caution_threshold,
+            self.max_continuous_threshold,
+            self.configuration,
+            self.status,
+            self.update_count,
+            bus
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::protocol::TemperatureEncoding;
+
+    const SENSOR_BUS_3: [u8; 32] = [
+        0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x67, 0x00, 0x00, 0x00, 0x5d, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x0b, 0x00, 0x23, 0x00, 0x00, 0x00, 0x83, 0xdb, 0x00, 0x00, 0x91, 0x23,
+        0xf4, 0xff,
+    ];
+
+    fn header() -> OcsdHeader {
+        OcsdHeader {
+            ocsd_version: OcsdVersion::Version2,
+            buffer_size: 0x0640,
+            max_option_cards: 10,
+            one_option_card_size: 0xa0,
+            buffer_start_address: 0x791f6040,
+            update_interval: 1,
+            buffers_in_use: 3,
+            ..Default::default()
+        }
+    }
+
+    fn device_bytes() -> Vec<u8> {
+        let mut bytes = OcsdDeviceHeader {
+            version: DeviceVersion::Version1,
+            pci_bus: 0x03,
+            pci_device: 0x00,
+            flags_caps: OcsdDeviceFlags::HOST_REPORTED,
+            ..Default::default()
+        }
+        .to_bytes();
+        bytes.extend_from_slice(&[0x00; 32]);
+        bytes.extend_from_slice(&SENSOR_BUS_3);
+        bytes.extend_from_slice(&[0x00; 32]);
+        bytes
+    }
+
+    #[test]
+    fn sensor_checked() {
+        let sensor = OcsdSensor::from_bytes_checked(&SENSOR_BUS_3, 0x03).unwrap();
+        assert_eq!(sensor.bus, Some(0x03));
+        assert_eq!(sensor.reading.degrees(), 35);
+
+        assert_eq!(
+            OcsdSensor::from_bytes_checked(&SENSOR_BUS_3, 0x04).err(),
+            Some(DecodeError::BadChecksum(ChecksumMismatch {
+                structure: OcsdStructure::Sensor(None),
+                expected: 0xfff42390,
+                found: 0xfff42391,
+            }))
+        );
+    }
+
+    #[test]
+    fn temperature_encodings() {
+        // every temperature observed in the real dump decodes alike
+        let sensor = OcsdSensor::from_bytes_checked(&SENSOR_BUS_3, 0x03).unwrap();
+        for encoding in [TemperatureEncoding::Unsigned, TemperatureEncoding::Signed] {
+            assert_eq!(encoding.decode(sensor.reading), 35);
+            assert_eq!(encoding.decode(sensor.caution_threshold), 103);
+            assert_eq!(encoding.decode(sensor.max_continuous_threshold), 93);
+        }
+        assert_eq!(
+            TemperatureEncoding::for_version(OcsdVersion::Version2),
+            TemperatureEncoding::Unsigned
+        );
+
+        // raw values are kept as read, whatever the encoding
+        let sensor = OcsdSensor {
+            reading: Celsius::from_raw(0xc8),
+            ..sensor
+        };
+        let bytes = sensor.to_bytes();
+        assert_eq!(bytes[20], 0xc8);
+        let sensor = OcsdSensor::from_bytes_checked(&bytes, 0x03).unwrap();
+        assert_eq!(TemperatureEncoding::Unsigned.decode(sensor.reading), 200);
+        assert_eq!(TemperatureEncoding::Signed.decode(sensor.reading), -56);
+        assert_eq!(TemperatureEncoding::Offset(40).decode(sensor.reading), 160);
+    }
+
+    #[test]
+    fn sensor_states() {
+        let sensor = OcsdSensor::from_bytes_checked(&SENSOR_BUS_3, 0x03).unwrap();
+        assert_eq!(sensor.state(), OcsdSensorState::Ok);
+        assert_eq!(sensor.valid_reading(), Some(sensor.reading));
+        assert_eq!(OcsdSensor::default().state(), OcsdSensorState::NotPresent);
+
+        // every state round trips, including with a reading of 0 °C
+        let zero = OcsdSensor {
+            reading: Celsius::new(0).unwrap(),
+            ..sensor.clone()
+        };
+        for original in [&sensor, &zero] {
+            for state in [
+                OcsdSensorState::Ok,
+                OcsdSensorState::Stale,
+                OcsdSensorState::Failed,
+                OcsdSensorState::NotPresent,
+                OcsdSensorState::Disabled,
+            ] {
+                let mut encoded = original.clone();
+                encoded.set_state(state);
+                let decoded = OcsdSensor::from_bytes_checked(&encoded.to_bytes(), 0x03).unwrap();
+                assert_eq!(decoded.state(), state, "{}", state);
+                assert_eq!(decoded, encoded);
+                assert!(decoded.status.contains(OcsdSensorStatus::WithChecksum));
+                if state.has_reading() {
+                    assert_eq!(decoded.reading, original.reading);
+                }
+                assert_eq!(
+                    decoded.valid_reading().is_some(),
+                    state == OcsdSensorState::Ok
+                );
+            }
+        }
+
+        // iLO sees a stale sensor as failed, but the last reading is kept,
+        // even a raw 0
+        assert_eq!(zero.reading.raw_value(), 0);
+        let mut stale = zero.clone();
+        stale.set_state(OcsdSensorState::Stale);
+        assert!(!stale.status.contains(OcsdSensorStatus::NotFailed));
+        assert_eq!(stale.status.bits(), 0x001a);
+        stale.set_state(OcsdSensorState::Ok);
+        assert!(!stale.status.contains(OcsdSensorStatus::Stale));
+
+        // other flags are kept
+        let mut failed = OcsdSensor {
+            status: OcsdSensorStatus::from(0x802b),
+            ..sensor
+        };
+        failed.set_state(OcsdSensorState::Failed);
+        assert_eq!(failed.status.bits(), 0x802a);
+        assert_eq!(failed.reading.raw_value(), 0);
+        assert_eq!(OcsdSensorState::NotPresent.to_string(), "not_present");
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            header().to_string(),
+            "version2, 10 option cards of 0xa0 bytes at 0x791f6040, 3 in use, update interval 1"
+        );
+        let device = OcsdDevice::try_from(device_bytes().as_slice()).unwrap();
+        assert_eq!(
+            device.sensors[1].to_string(),
+            "thermal internal_to_asic, reading 35°C (caution 103°C, max continuous 93°C), \
+             configuration 0x0000, status [not_failed, present, with_checksum], \
+             update count 56195, bus 03"
+        );
+        assert_eq!(device.sensors[0].to_string(), "null");
+        #[rustfmt::skip]
+        let table = [
+            "version1, pci 03:00, flags/caps 0x00000010 (host_reported)",
+            "  #  type     location          reading  caution  max continuous  update count  configuration  status",
+            "  0  null",
+            "  1  thermal  internal_to_asic     35°C    103°C            93°C         56195  0x0000         [not_failed, present, with_checksum]",
+            "  2  null",
+        ];
+        assert_eq!(device.to_string(), table.join("\n"));
+
+        // decoding an encoded device gives back an equal value
+        let copy = device.clone();
+        assert_eq!(
+            OcsdDevice::try_from(copy.to_bytes().as_slice()),
+            Ok(device.clone())
+        );
+        assert_ne!(device.sensors[0], device.sensors[1]);
+    }
+
+    #[test]
+    fn null_sensor_checked() {
+        let sensor = OcsdSensor::from_bytes_checked(&[0x00; 32], 0x03).unwrap();
+        assert_eq!(sensor.bus, None);
+    }
+
+    #[test]
+    fn header_checked() {
+        let mut bytes = header().to_bytes();
+        assert!(OcsdHeader::from_bytes_checked(&bytes).is_ok());
+
+        // buffers_in_use
+        bytes[56] = 4;
+        match OcsdHeader::from_bytes_checked(&bytes).err() {
+            Some(DecodeError::BadChecksum(mismatch)) => {
+                assert_eq!(mismatch.structure, OcsdStructure::Header);
+                assert_eq!(mismatch.expected, mismatch.found.wrapping_sub(1));
+            }
+            _ => panic!("expected checksum mismatch"),
+        }
+    }
+
+    #[test]
+    fn header_unknown_version() {
+        let header = OcsdHeader {
+            ocsd_version: OcsdVersion::Unknown,
+            ..header()
+        };
+        let bytes = header.to_bytes();
+        assert!(OcsdHeader::from_bytes_checked(&bytes).is_ok());
+        assert_eq!(
+            OcsdHeader::try_from(&bytes[..]).err(),
+            Some(DecodeError::UnknownVersion {
+                structure: OcsdStructure::Header,
+                version: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn header_length() {
+        let bytes = header().to_bytes();
+        assert_eq!(
+            OcsdHeader::try_from(&bytes[..63]).err(),
+            Some(DecodeError::ShortBuffer {
+                structure: OcsdStructure::Header,
+                expected: 64,
+                actual: 63,
+            })
+        );
+        assert_eq!(
+            OcsdHeader::try_from(&[&bytes[..], &[0x00]].concat()[..]).err(),
+            Some(DecodeError::OversizedBuffer {
+                structure: OcsdStructure::Header,
+                expected: 64,
+                actual: 65,
+            })
+        );
+        assert!(OcsdHeader::try_from(&[][..]).is_err());
+    }
+
+    #[test]
+    fn unaligned() {
+        let mut bytes = vec![0x00];
+        bytes.append(&mut device_bytes());
+        assert!(OcsdDevice::try_from(&bytes[1..]).is_ok());
+    }
+
+    #[test]
+    fn device_checked() {
+        let mut bytes = device_bytes();
+        let device = OcsdDevice::try_from(&bytes[..]).unwrap();
+        assert_eq!(device.sensors[1].bus, Some(0x03));
+
+        // sensor 1 reading
+        bytes[64 + 32 + 20] += 1;
+        match OcsdDevice::try_from(&bytes[..]).err() {
+            Some(DecodeError::BadChecksum(mismatch)) => {
+                assert_eq!(mismatch.structure, OcsdStructure::Sensor(Some(1)))
+            }
+            _ => panic!("expected checksum mismatch"),
+        }
+
+        // device header pci_bus
+        bytes[4] = 0x04;
+        match OcsdDevice::try_from(&bytes[..]).err() {
+            Some(DecodeError::BadChecksum(mismatch)) => {
+                assert_eq!(mismatch.structure, OcsdStructure::DeviceHeader)
+            }
+            _ => panic!("expected checksum mismatch"),
+        }
+    }
+
+    #[test]
+    fn device_length() {
+        let bytes = device_bytes();
+        assert_eq!(
+            OcsdDevice::try_from(&bytes[..60]).err(),
+            Some(DecodeError::ShortBuffer {
+                structure: OcsdStructure::Device,
+                expected: 64,
+                actual: 60,
+            })
+        );
+        // trailing bytes which don't make up a whole sensor are ignored
+        let device = OcsdDevice::try_from(&bytes[..100]).unwrap();
+        assert_eq!(device.sensors.len(), 1);
+        assert_eq!(device, OcsdDevice::try_from(&bytes[..96]).unwrap());
+        assert!(OcsdSensor::try_from(&bytes[..31]).is_err());
+        assert!(OcsdDeviceHeader::try_from(&bytes[..64]).is_ok());
+    }
+
+    #[test]
+    fn sensor_count() {
+        assert_eq!(header().sensors_per_device(), 3);
+        let header = OcsdHeader {
+            one_option_card_size: 0xe0,
+            ..header()
+        };
+        assert_eq!(header.sensors_per_device(), 5);
+        assert_eq!(OcsdDevice::sensor_capacity(0x30), 0);
+
+        // a smaller record
+        let bytes = device_bytes();
+        let device = OcsdDevice::try_from(&bytes[..96]).unwrap();
+        assert_eq!(device.sensors.len(), 1);
+        assert_eq!(device.to_bytes(), bytes[..96]);
+
+        // a larger record, with the sensor at the end
+        let mut bytes = device_bytes();
+        bytes.extend_from_slice(&[0x00; 32]);
+        bytes.extend_from_slice(&SENSOR_BUS_3);
+        let device = OcsdDevice::try_from(&bytes[..]).unwrap();
+        assert_eq!(device.sensors.len(), 5);
+        assert_eq!(device.sensors[4].reading.degrees(), 35);
+        assert_eq!(device.record_size(), 0xe0);
+        assert_eq!(device.to_bytes(), bytes);
+        assert_eq!(OcsdDevice::from_bytes(&bytes).sensors.len(), 5);
+    }
+
+    /// Pins the memory representation of each structure, with every field set
+    /// to a distinct value. Multi-byte values are little-endian on any host.
+    #[test]
+    fn byte_layout() {
+        #[rustfmt::skip]
+        const HEADER: [u8; 64] = [
+            0x02, 0x00, 0x00, 0x00, // ocsd_version
+            0x40, 0x06, 0x00, 0x00, // buffer_size
+            0x0a, 0x00, 0x00, 0x00, // max_option_cards
+            0xa0, 0x00, 0x00, 0x00, // one_option_card_size
+            0x40, 0x60, 0x1f, 0x79, // buffer_start_address
+            0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f, // padding_0
+            0x01, 0x00, 0x00, 0x00, // update_interval
+            0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d, 0x2e, 0x2f,
+            0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, // padding_1
+            0x03, 0x00, 0x00, 0x00, // buffers_in_use
+            0xd0, 0x98, 0xe0, 0x86, // checksum
+        ];
+        #[rustfmt::skip]
+        const DEVICE_HEADER: [u8; 64] = [
+            0x01, 0x00, 0x00, 0x00, // version
+            0x04, 0x00, 0x00, 0x00, // pci_bus
+            0x02, 0x00, 0x00, 0x00, // pci_device
+            0x0c, 0x0d, 0x0e, 0x0f, // unknown_1
+            0x10, 0x11, 0x12, 0x13, // unknown_2
+            0x10, 0x00, 0x00, 0x00, // flags_caps
+            0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f, 0x20, 0x21, 0x22, 0x23,
+            0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d, 0x2e, 0x2f,
+            0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x3b, // unknown_3
+            0x65, 0x6f, 0x64, 0x59, // checksum
+        ];
+        #[rustfmt::skip]
+        const SENSOR: [u8; 32] = [
+            0x01, 0x00, 0x00, 0x00, // sensor_type
+            0x05, 0x00, 0x00, 0x00, // sensor_location
+            0x5a, 0x00, 0x00, 0x00, // caution_threshold
+            0x50, 0x00, 0x00, 0x00, // max_continuous_threshold
+            0x34, 0x12, 0x0b, 0x00, // configuration, status
+            0x28, 0x00, 0x00, 0x00, // reading
+            0xef, 0xbe, 0x00, 0x00, // update_count
+            0x01, 0x2e, 0xf4, 0xff, // checksum, including bus 0x04
+        ];
+
+        let header = OcsdHeader {
+            padding_0: [0x17161514, 0x1b1a1918, 0x1f1e1d1c],
+            padding_1: [0x27262524, 0x2b2a2928, 0x2f2e2d2c, 0x33323130, 0x37363534],
+            ..header()
+        };
+        assert_eq!(header.to_bytes(), HEADER);
+        let decoded = OcsdHeader::try_from(&HEADER[..]).unwrap();
+        assert_eq!(decoded.buffer_size, 0x0640);
+        assert_eq!(decoded.buffer_start_address, 0x791f6040);
+        assert_eq!(decoded.padding_1, header.padding_1);
+
+        let device_header = OcsdDeviceHeader {
+            version: DeviceVersion::Version1,
+            pci_bus: 0x04,
+            pci_device: 0x02,
+            unknown_1: 0x0f0e0d0c,
+            unknown_2: 0x13121110,
+            flags_caps: OcsdDeviceFlags::HOST_REPORTED,
+            unknown_3: [
+                0x1b1a1918, 0x1f1e1d1c, 0x23222120, 0x27262524, 0x2b2a2928, 0x2f2e2d2c, 0x33323130,
+                0x37363534, 0x3b3a3938,
+            ],
+        };
+        assert_eq!(device_header.to_bytes(), DEVICE_HEADER);
+        let decoded = OcsdDeviceHeader::try_from(&DEVICE_HEADER[..]).unwrap();
+        assert_eq!(decoded.unknown_1, 0x0f0e0d0c);
+        assert_eq!(decoded.flags_caps, OcsdDeviceFlags::HOST_REPORTED);
+        assert_eq!(decoded.unknown_3, device_header.unknown_3);
+
+        let sensor = OcsdSensor {
+            sensor_type: OcsdSensorType::Thermal,
+            sensor_location: OcsdSensorLocation::OnboardOther,
+            configuration: 0x1234.into(),
+            status: OcsdSensorStatus::from(0x000b),
+            max_continuous_threshold: Celsius::new(80).unwrap(),
+            caution_threshold: Celsius::new(90).unwrap(),
+            reading: Celsius::new(40).unwrap(),
+            update_count: 0xbeef,
+            bus: Some(0x04),
+        };
+        assert_eq!(sensor.to_bytes(), SENSOR);
+        let decoded = OcsdSensor::from_bytes_checked(&SENSOR, 0x04).unwrap();
+        assert_eq!(decoded.configuration.bits(), 0x1234);
+        assert_eq!(decoded.status, sensor.status);
+        assert_eq!(decoded.update_count, 0xbeef);
+        assert_eq!(decoded.caution_threshold.degrees(), 90);
+        assert_eq!(decoded.max_continuous_threshold.degrees(), 80);
+    }
+
+    #[test]
+    fn configuration() {
+        let configuration = OcsdSensorConfiguration::from(0x8012);
+        assert_eq!(configuration.to_string(), "0x8012 (unknown 0x8012)");
+        assert_eq!(
+            format!("{:?}", configuration),
+            "OcsdSensorConfiguration(0x8012 (unknown 0x8012))"
+        );
+        assert_eq!(OcsdSensorConfiguration::default().to_string(), "0x0000");
+        assert!(configuration.contains(0x0002.into()));
+        assert!(!configuration.contains(0x0003.into()));
+
+        // unknown bits survive encoding
+        let sensor = OcsdSensor {
+            configuration,
+            bus: Some(0x03),
+            ..Default::default()
+        };
+        let decoded = OcsdSensor::from_bytes_checked(&sensor.to_bytes(), 0x03).unwrap();
+        assert_eq!(decoded.configuration, configuration);
+
+        struct Named(u32);
+        impl Display for Named {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                fmt_bits(f, self.0, 10, [(0x10, "a"), (0x03, "b")].into_iter())
+            }
+        }
+        assert_eq!(Named(0x13).to_string(), "0x00000013 (a, b)");
+        assert_eq!(
+            Named(0x112).to_string(),
+            "0x00000112 (a, unknown 0x00000102)"
+        );
+    }
+
+    #[test]
+    fn device_flags() {
+        let header = OcsdDeviceHeader::host_reported(0x04, 0x01);
+        assert_eq!(header.flags_caps.bits(), 0x00000010);
+        assert_eq!(header.flags_caps.to_string(), "0x00000010 (host_reported)");
+        assert_eq!(header.to_bytes()[20..24], [0x10, 0x00, 0x00, 0x00]);
+
+        // unknown bits survive encoding
+        let flags = OcsdDeviceFlags::HOST_REPORTED | 0x8000_0001.into();
+        assert!(flags.contains(OcsdDeviceFlags::HOST_REPORTED));
+        assert_eq!(flags.unknown_bits(), 0x8000_0001);
+        assert_eq!(
+            format!("{:?}", flags),
+            "OcsdDeviceFlags(0x80000011 (host_reported, unknown 0x80000001))"
+        );
+        let header = OcsdDeviceHeader {
+            flags_caps: flags,
+            ..header
+        };
+        let decoded = OcsdDeviceHeader::from_bytes_checked(&header.to_bytes()).unwrap();
+        assert_eq!(decoded.flags_caps, flags);
+        assert_eq!(OcsdDeviceFlags::default().to_string(), "0x00000000");
+    }
+
+    #[test]
+    fn preserve_reserved() {
+        let mut header = header();
+        header.padding_0 = [1, 2, 3];
+        header.padding_1 = [4, 5, 6, 7, 8];
+        let bytes = header.to_bytes();
+        assert_eq!(bytes[20..24], [1, 0, 0, 0]);
+        assert_eq!(bytes[52..56], [8, 0, 0, 0]);
+
+        let mut header = OcsdHeader::from_bytes_checked(&bytes).unwrap();
+        header.buffers_in_use = 4;
+        let modified = header.to_bytes();
+        assert_eq!(modified[20..56], bytes[20..56]);
+        assert_eq!(
+            OcsdHeader::try_from(&modified[..]).unwrap().buffers_in_use,
+            4
+        );
+
+        let mut device_header = OcsdDeviceHeader::from_bytes(&device_bytes());
+        device_header.unknown_1 = 0x11;
+        device_header.unknown_2 = 0x22;
+        device_header.unknown_3[8] = 0x33;
+        let bytes = device_header.to_bytes();
+        assert_eq!(bytes[12..20], [0x11, 0, 0, 0, 0x22, 0, 0, 0]);
+        assert_eq!(bytes[56..60], [0x33, 0, 0, 0]);
+
+        // the checksum covers the unknown words
+        let mut device_header = OcsdDeviceHeader::try_from(&bytes[..]).unwrap();
+        device_header.pci_bus = 0x04;
+        let modified = device_header.to_bytes();
+        assert_eq!(modified[12..20], bytes[12..20]);
+        assert_eq!(modified[56..60], bytes[56..60]);
+        let mut zeroed = modified.clone();
+        zeroed[12..20].fill(0);
+        assert!(OcsdDeviceHeader::try_from(&modified[..]).is_ok());
+        assert!(OcsdDeviceHeader::try_from(&zeroed[..]).is_err());
+    }
+
+    #[test]
+    fn empty_device_slot() {
+        assert!(OcsdDevice::try_from(&[0x00; 160][..]).is_ok());
+    }
+
+    #[test]
+    fn null_sensor_size() {
+        assert_eq!(OcsdSensor::default().to_bytes(), vec![0x00; 32]);
+        for sensor_count in [1, DEFAULT_SENSOR_COUNT, 5] {
+            let device = OcsdDevice {
+                header: OcsdDeviceHeader {
+                    version: DeviceVersion::Version1,
+                    pci_bus: 0x03,
+                    pci_device: 0x00,
+                    flags_caps: OcsdDeviceFlags::HOST_REPORTED,
+                    ..Default::default()
+                },
+                sensors: (0..sensor_count).map(|_| Default::default()).collect(),
+            };
+            let bytes = device.to_bytes();
+            assert_eq!(bytes.len(), OcsdDevice::size_with_sensors(sensor_count));
+            assert_eq!(bytes.len(), device.record_size());
+            assert_eq!(OcsdDevice::from_bytes_checked(&bytes).unwrap(), device);
+        }
+        assert_eq!(
+            OcsdDevice::size_with_sensors(DEFAULT_SENSOR_COUNT),
+            OcsdDevice::memory_size()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let bytes = device_bytes();
+        let device = OcsdDevice::try_from(&bytes[..]).unwrap();
+        let json = serde_json::to_value(&device).unwrap();
+        assert_eq!(
+            json["header"],
+            serde_json::json!({
+                "version": "version1",
+                "pci_bus": 3,
+                "pci_device": 0,
+                "unknown_1": 0,
+                "unknown_2": 0,
+                "flags_caps": 16,
+                "unknown_3": [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            })
+        );
+        assert_eq!(
+            json["sensors"][1],
+            serde_json::json!({
+                "sensor_type": "thermal",
+                "sensor_location": "internal_to_asic",
+                "configuration": 0,
+                "status": ["not_failed", "present", "with_checksum"],
+                "max_continuous_threshold": 93,
+                "caution_threshold": 103,
+                "reading": 35,
+                "update_count": 56195,
+                "bus": 3,
+            })
+        );
+        let decoded: OcsdDevice = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        // reserved words may be omitted
+        let decoded: OcsdDeviceHeader = serde_json::from_value(serde_json::json!({
+            "version": "version1",
+            "pci_bus": 3,
+            "pci_device": 0,
+            "flags_caps": 16,
+        }))
+        .unwrap();
+        assert_eq!(decoded.to_bytes(), bytes[..64]);
+
+        let json = serde_json::to_value(header()).unwrap();
+        assert_eq!(json["ocsd_version"], "version2");
+        let decoded: OcsdHeader = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.to_bytes(), header().to_bytes());
+
+        // bits without a name are kept
+        let status = OcsdSensorStatus::from(0x8029);
+        let json = serde_json::to_value(status).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!(["not_failed", "with_checksum", "0x0020", "0x8000"])
+        );
+        assert_eq!(
+            serde_json::from_value::<OcsdSensorStatus>(json).unwrap(),
+            status
+        );
+        assert!(serde_json::from_str::<OcsdSensorStatus>(r#"["broken"]"#).is_err());
+        assert!(serde_json::from_str::<Celsius>("300").is_err());
+    }
+
+    type SensorFields = (u8, u32, u16, u16, [u8; 3], u16, Option<u8>);
+
+    fn sensor(fields: SensorFields) -> OcsdSensor {
+        let (sensor_type, location, configuration, status, temps, update_count, bus) = fields;
+        OcsdSensor {
+            sensor_type: sensor_type.into(),
+            sensor_location: location.into(),
+            configuration: configuration.into(),
+            status: status.into(),
+            max_continuous_threshold: Celsius::from_raw(temps[0]),
+            caution_threshold: Celsius::from_raw(temps[1]),
+            reading: Celsius::from_raw(temps[2]),
+            update_count,
+            bus,
+        }
+    }
+
+    fn device_header(fields: (u8, u8, u8, u32)) -> OcsdDeviceHeader {
+        let (version, pci_bus, pci_device, flags_caps) = fields;
+        OcsdDeviceHeader {
+            version: version.into(),
+            pci_bus,
+            pci_device,
+            flags_caps: flags_caps.into(),
+            ..Default::default()
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn header_size(
+            version in any::<u8>(),
+            buffer_size in any::<u16>(),
+            fields in any::<[u8; 4]>(),
+            buffer_start_address in any::<u32>(),
+        ) {
+            let header = OcsdHeader {
+                ocsd_version: version.into(),
+                buffer_size,
+                max_option_cards: fields[0],
+                one_option_card_size: fields[1],
+                buffer_start_address,
+                update_interval: fields[2],
+                buffers_in_use: fields[3],
+                ..Default::default()
+            };
+            let bytes = header.to_bytes();
+            prop_assert_eq!(bytes.len(), OcsdHeader::memory_size());
+            prop_assert_eq!(OcsdHeader::from_bytes_checked(&bytes)?.to_bytes(), bytes);
+        }
+
+        #[test]
+        fn device_header_size(fields in any::<(u8, u8, u8, u32)>()) {
+            let bytes = device_header(fields).to_bytes();
+            prop_assert_eq!(bytes.len(), OcsdDeviceHeader::memory_size());
+            prop_assert_eq!(OcsdDeviceHeader::from_bytes_checked(&bytes)?.to_bytes(), bytes);
+        }
+
+        #[test]
+        fn sensor_size(fields in any::<SensorFields>()) {
+            let bytes = sensor(fields).to_bytes();
+            prop_assert_eq!(bytes.len(), OcsdSensor::memory_size());
+            prop_assert_eq!(OcsdSensor::from_bytes(&bytes).to_bytes().len(), bytes.len());
+        }
+
+        #[test]
+        fn device_size(
+            header_fields in any::<(u8, u8, u8, u32)>(),
+            sensor_fields in prop::collection::vec(any::<SensorFields>(), 0..8),
+        ) {
+            let header = device_header(header_fields);
+            let bus = header.pci_bus;
+            let device = OcsdDevice {
+                header,
+                sensors: sensor_fields.into_iter().map(|fields| {
+                    let sensor = sensor(fields);
+                    OcsdSensor {
+                        bus: sensor.bus.map(|_| bus),
+                        ..sensor
+                    }
+                }).collect(),
+            };
+            let bytes = device.to_bytes();
+            prop_assert_eq!(bytes.len(), OcsdDevice::size_with_sensors(device.sensors.len()));
+            prop_assert_eq!(bytes.len(), device.record_size());
+            prop_assert_eq!(OcsdDevice::sensor_capacity(bytes.len()), device.sensors.len());
+            prop_assert_eq!(OcsdDevice::from_bytes_checked(&bytes)?.to_bytes(), bytes);
+        }
     }
 }
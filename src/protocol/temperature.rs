@@ -1,4 +1,6 @@
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use super::ocsd::OcsdVersion;
 
 /// Error used when a constructed temperature value does not fit
 /// into the OCSD representation's range.
@@ -13,20 +15,256 @@ impl Display for TempOutOfRange {
 
 impl Error for TempOutOfRange {}
 
-/// Represents a signed integer temperature in degrees Celsius,
-/// stored as a single-byte raw value.
-#[derive(Default)]
+/// Error used when a [TemperatureEncoding] cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEncoding(pub String);
+
+impl Display for InvalidEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid temperature encoding {:?}: expected unsigned, signed or offset:<degrees>",
+            self.0
+        )
+    }
+}
+
+impl Error for InvalidEncoding {}
+
+/// Encoding of a temperature in the single-byte raw OCSD fields.
+///
+/// Every sensor observed so far reports readings and thresholds between 0
+/// and 127°C, which all encodings other than [Offset](TemperatureEncoding::Offset)
+/// agree on. The fields are unsigned bytes, and iLO has not been seen to
+/// report a temperature below zero, so [Unsigned](TemperatureEncoding::Unsigned)
+/// is assumed by default; the others are available for hardware that turns
+/// out to differ.
+///
+/// As text, e.g. in configuration files, an encoding is written as
+/// `unsigned`, `signed` or `offset:<degrees>`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub enum TemperatureEncoding {
+    /// Whole degrees from 0 to 255°C
+    #[default]
+    Unsigned,
+    /// Two's complement whole degrees from -128 to 127°C
+    Signed,
+    /// Whole degrees plus the provided offset, e.g. from -40 to 215°C with
+    /// an offset of 40
+    Offset(u8),
+}
+
+impl TemperatureEncoding {
+    /// Encoding of temperatures in buffers of the provided OCSD version.
+    pub const fn for_version(version: OcsdVersion) -> Self {
+        match version {
+            OcsdVersion::Version2 | OcsdVersion::Unknown => Self::Unsigned,
+        }
+    }
+
+    /// Lowest temperature which can be encoded, in degrees.
+    pub const fn min(self) -> i16 {
+        match self {
+            Self::Unsigned => 0,
+            Self::Signed => i8::MIN as i16,
+            Self::Offset(offset) => -(offset as i16),
+        }
+    }
+
+    /// Highest temperature which can be encoded, in degrees.
+    pub const fn max(self) -> i16 {
+        match self {
+            Self::Unsigned => u8::MAX as i16,
+            Self::Signed => i8::MAX as i16,
+            Self::Offset(offset) => u8::MAX as i16 - offset as i16,
+        }
+    }
+
+    /// Encodes a temperature in degrees, returning TempOutOfRange if it is
+    /// outside of the range from [min](TemperatureEncoding::min) to
+    /// [max](TemperatureEncoding::max).
+    ///
+    /// # Examples
+    /// ```
+    /// # use ocsd::protocol::error::TempOutOfRange;
+    /// use ocsd::protocol::TemperatureEncoding;
+    ///
+    /// # fn main() -> Result<(), TempOutOfRange> {
+    /// assert_eq!(TemperatureEncoding::Unsigned.encode(200)?.raw_value(), 0xc8);
+    /// assert_eq!(TemperatureEncoding::Signed.encode(-56)?.raw_value(), 0xc8);
+    /// assert!(TemperatureEncoding::Signed.encode(200).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn encode(self, degrees: i16) -> Result<Celsius, TempOutOfRange> {
+        self.encode_degrees(degrees.into())
+    }
+
+    /// Encodes a temperature in degrees, clamping it to the range from
+    /// [min](TemperatureEncoding::min) to [max](TemperatureEncoding::max).
+    pub fn encode_saturating(self, degrees: i16) -> Celsius {
+        self.encode_degrees_saturating(degrees.into())
+    }
+
+    /// Encodes a temperature in millidegrees, rounded to whole degrees; see
+    /// [Celsius::from_millidegrees].
+    pub fn encode_millidegrees(
+        self,
+        millidegrees: i64,
+        rounding: Rounding,
+    ) -> Result<Celsius, TempOutOfRange> {
+        self.encode_degrees(rounding.round_millidegrees(millidegrees))
+    }
+
+    /// Decodes a temperature to degrees.
+    ///
+    /// # Examples
+    /// ```
+    /// use ocsd::protocol::{Celsius, TemperatureEncoding};
+    ///
+    /// let temperature = Celsius::from_raw(0xc8);
+    /// assert_eq!(TemperatureEncoding::Unsigned.decode(temperature), 200);
+    /// assert_eq!(TemperatureEncoding::Signed.decode(temperature), -56);
+    /// assert_eq!(TemperatureEncoding::Offset(40).decode(temperature), 160);
+    /// ```
+    pub const fn decode(self, temperature: Celsius) -> i16 {
+        match self {
+            Self::Unsigned => temperature.raw as i16,
+            Self::Signed => temperature.raw as i8 as i16,
+            Self::Offset(offset) => temperature.raw as i16 - offset as i16,
+        }
+    }
+
+    fn encode_degrees(self, degrees: i64) -> Result<Celsius, TempOutOfRange> {
+        if degrees < self.min().into() || degrees > self.max().into() {
+            return Err(TempOutOfRange);
+        }
+        let raw = match self {
+            Self::Unsigned | Self::Signed => degrees,
+            Self::Offset(offset) => degrees + i64::from(offset),
+        };
+        // two's complement for negative values of the signed encoding
+        Ok(Celsius { raw: raw as u8 })
+    }
+
+    fn encode_degrees_saturating(self, degrees: i64) -> Celsius {
+        let degrees = degrees.clamp(self.min().into(), self.max().into());
+        self.encode_degrees(degrees)
+            .expect("clamped to the representable range")
+    }
+}
+
+impl FromStr for TemperatureEncoding {
+    type Err = InvalidEncoding;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unsigned" => Ok(Self::Unsigned),
+            "signed" => Ok(Self::Signed),
+            _ => s
+                .strip_prefix("offset:")
+                .and_then(|offset| offset.parse().ok())
+                .map(Self::Offset)
+                .ok_or_else(|| InvalidEncoding(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for TemperatureEncoding {
+    type Error = InvalidEncoding;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Shows the encoding as parsed by [FromStr], e.g. `offset:40`.
+impl Display for TemperatureEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsigned => f.pad("unsigned"),
+            Self::Signed => f.pad("signed"),
+            Self::Offset(offset) => f.pad(&format!("offset:{}", offset)),
+        }
+    }
+}
+
+impl From<TemperatureEncoding> for String {
+    fn from(value: TemperatureEncoding) -> Self {
+        value.to_string()
+    }
+}
+
+/// Rounding applied when converting a fractional temperature to whole degrees.
+///
+/// Fractional temperatures are first resolved to millidegrees, so that e.g.
+/// 40.0000001°C is not rounded up to 41°C by [Rounding::Ceil].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rounding {
+    /// Round to the nearest degree, with halves rounded away from zero
+    #[default]
+    Nearest,
+    /// Round down, towards negative infinity
+    Floor,
+    /// Round up, towards positive infinity
+    Ceil,
+}
+
+impl Rounding {
+    /// Rounds a temperature in millidegrees to whole degrees.
+    fn round_millidegrees(self, millidegrees: i64) -> i64 {
+        let degrees = millidegrees.div_euclid(1000);
+        let round_up = match (self, millidegrees.rem_euclid(1000)) {
+            (_, 0) | (Self::Floor, _) => false,
+            (Self::Ceil, _) => true,
+            // halves are rounded away from zero
+            (Self::Nearest, 500) => millidegrees > 0,
+            (Self::Nearest, remainder) => remainder > 500,
+        };
+        degrees + i64::from(round_up)
+    }
+}
+
+/// Represents an integer temperature in degrees Celsius, stored as a
+/// single-byte raw value.
+///
+/// The raw value is kept as read, so decoding and encoding an OCSD structure
+/// never changes it. Conversions to and from degrees use the default
+/// [TemperatureEncoding], unsigned; use the encoding's own methods for
+/// others.
+///
+/// With the `serde` feature, this is serialised as an integer number of degrees.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "i16", into = "i16")
+)]
 pub struct Celsius {
-    value: i8,
+    raw: u8,
 }
 
 impl Celsius {
-    const OFFSET: i8 = 0;
+    const ENCODING: TemperatureEncoding = TemperatureEncoding::Unsigned;
+
+    /// Lowest representable temperature in the default encoding, 0°C.
+    pub const MIN: Self = Self { raw: u8::MIN };
+
+    /// Highest representable temperature in the default encoding, 255°C.
+    pub const MAX: Self = Self { raw: u8::MAX };
 
     /// Constructs a new Celsius value.
     ///
     /// Returns a Result of the constructed value, or TempOutOfRange
-    /// when the passed value cannot fit into the raw value field.
+    /// when the passed value cannot fit into the raw value field. In the
+    /// default unsigned encoding, this is from 0 to 255°C, so unlike with
+    /// the previous signed encoding, negative temperatures such as -1°C are
+    /// rejected; use [TemperatureEncoding::encode] to encode them.
     ///
     /// # Examples
     /// ```
@@ -40,16 +278,120 @@ impl Celsius {
     /// # }
     /// ```
     pub fn new(degrees: i16) -> Result<Self, TempOutOfRange> {
-        let value = degrees + Self::OFFSET as i16;
-        match value.try_into() {
-            Ok(value) => Ok(Self { value }),
-            Err(_) => Err(TempOutOfRange),
+        Self::ENCODING.encode(degrees)
+    }
+
+    /// Constructs a new Celsius value, clamping `degrees` to the range from
+    /// [MIN](Celsius::MIN) to [MAX](Celsius::MAX).
+    ///
+    /// # Examples
+    /// ```
+    /// use ocsd::protocol::Celsius;
+    ///
+    /// assert_eq!(Celsius::new_saturating(300), Celsius::MAX);
+    /// ```
+    pub fn new_saturating(degrees: i16) -> Self {
+        Self::ENCODING.encode_saturating(degrees)
+    }
+
+    /// Constructs a Celsius value from millidegrees, as reported by hwmon
+    /// `temp*_input` files.
+    ///
+    /// Returns TempOutOfRange when the rounded value cannot fit into the raw
+    /// value field.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ocsd::protocol::error::TempOutOfRange;
+    /// use ocsd::protocol::{Celsius, Rounding};
+    ///
+    /// # fn main() -> Result<(), TempOutOfRange> {
+    /// assert_eq!(Celsius::from_millidegrees(39500, Rounding::Nearest)?.degrees(), 40);
+    /// assert_eq!(Celsius::from_millidegrees(39500, Rounding::Floor)?.degrees(), 39);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_millidegrees(
+        millidegrees: i64,
+        rounding: Rounding,
+    ) -> Result<Self, TempOutOfRange> {
+        Self::ENCODING.encode_millidegrees(millidegrees, rounding)
+    }
+
+    /// Constructs a Celsius value from millidegrees, clamping the rounded
+    /// value to the range from [MIN](Celsius::MIN) to [MAX](Celsius::MAX).
+    pub fn from_millidegrees_saturating(millidegrees: i64, rounding: Rounding) -> Self {
+        Self::ENCODING.encode_degrees_saturating(rounding.round_millidegrees(millidegrees))
+    }
+
+    /// Constructs a Celsius value from fractional degrees, as reported by
+    /// e.g. `nvidia-smi` or IPMI.
+    ///
+    /// Returns TempOutOfRange when the rounded value cannot fit into the raw
+    /// value field, or `degrees` is NaN.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ocsd::protocol::error::TempOutOfRange;
+    /// use ocsd::protocol::{Celsius, Rounding};
+    ///
+    /// # fn main() -> Result<(), TempOutOfRange> {
+    /// assert_eq!(Celsius::from_f64(39.2, Rounding::Ceil)?.degrees(), 40);
+    /// assert!(Celsius::from_f64(f64::NAN, Rounding::Nearest).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_f64(degrees: f64, rounding: Rounding) -> Result<Self, TempOutOfRange> {
+        match f64_to_millidegrees(degrees) {
+            Some(millidegrees) => Self::from_millidegrees(millidegrees, rounding),
+            None => Err(TempOutOfRange),
+        }
+    }
+
+    /// Constructs a Celsius value from fractional degrees, clamping the
+    /// rounded value to the range from [MIN](Celsius::MIN) to
+    /// [MAX](Celsius::MAX).
+    ///
+    /// NaN gives [MAX](Celsius::MAX), so that an invalid reading errs on the
+    /// side of more cooling.
+    pub fn from_f64_saturating(degrees: f64, rounding: Rounding) -> Self {
+        match f64_to_millidegrees(degrees) {
+            Some(millidegrees) => Self::from_millidegrees_saturating(millidegrees, rounding),
+            None => Self::MAX,
         }
     }
 
+    /// Constructs a Celsius value from whole Kelvin, as reported by e.g. the
+    /// NVMe SMART log.
+    ///
+    /// Returns TempOutOfRange when the rounded value cannot fit into the raw
+    /// value field.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ocsd::protocol::error::TempOutOfRange;
+    /// use ocsd::protocol::{Celsius, Rounding};
+    ///
+    /// # fn main() -> Result<(), TempOutOfRange> {
+    /// // 313K is 39.85°C
+    /// assert_eq!(Celsius::from_kelvin(313, Rounding::Nearest)?.degrees(), 40);
+    /// assert_eq!(Celsius::from_kelvin(313, Rounding::Floor)?.degrees(), 39);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_kelvin(kelvin: u16, rounding: Rounding) -> Result<Self, TempOutOfRange> {
+        Self::from_millidegrees(kelvin_to_millidegrees(kelvin), rounding)
+    }
+
+    /// Constructs a Celsius value from whole Kelvin, clamping the rounded
+    /// value to the range from [MIN](Celsius::MIN) to [MAX](Celsius::MAX).
+    pub fn from_kelvin_saturating(kelvin: u16, rounding: Rounding) -> Self {
+        Self::from_millidegrees_saturating(kelvin_to_millidegrees(kelvin), rounding)
+    }
+
     /// Returns the temperature as raw OCSD representation.
     pub fn raw_value(&self) -> u8 {
-        self.value as u8
+        self.raw
     }
 
     /// Returns the temperature as degrees.
@@ -67,7 +409,7 @@ impl Celsius {
     /// # }
     /// ```
     pub fn degrees(&self) -> i16 {
-        self.value as i16 - Self::OFFSET as i16
+        Self::ENCODING.decode(*self)
     }
 
     /// Constructs a new Celsius value from the raw OCSD representation.
@@ -85,7 +427,39 @@ impl Celsius {
     /// # }
     /// ```
     pub fn from_raw(value: u8) -> Self {
-        Self { value: value as i8 }
+        Self { raw: value }
+    }
+}
+
+/// Converts fractional degrees to millidegrees, saturating at the limits of
+/// i64. Returns [None] for NaN.
+fn f64_to_millidegrees(degrees: f64) -> Option<i64> {
+    // `as` saturates, and infinite values are out of range either way
+    (!degrees.is_nan()).then(|| (degrees * 1000.0).round() as i64)
+}
+
+fn kelvin_to_millidegrees(kelvin: u16) -> i64 {
+    i64::from(kelvin) * 1000 - 273_150
+}
+
+impl TryFrom<i16> for Celsius {
+    type Error = TempOutOfRange;
+
+    fn try_from(degrees: i16) -> Result<Self, Self::Error> {
+        Self::new(degrees)
+    }
+}
+
+impl From<Celsius> for i16 {
+    fn from(value: Celsius) -> Self {
+        value.degrees()
+    }
+}
+
+/// Shows the temperature in degrees, e.g. `40°C`.
+impl Display for Celsius {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!("{}°C", self.degrees()))
     }
 }
 
@@ -97,7 +471,224 @@ mod tests {
     fn temperature() {
         assert_eq!(Celsius::new(0).unwrap().raw_value(), 0);
         assert_eq!(Celsius::new(40).unwrap().raw_value(), 40);
-        assert_eq!(Celsius::from_raw(40).value, Celsius::new(40).unwrap().value);
-        assert_eq!(Celsius::new(-1).unwrap().raw_value(), 255);
+        assert_eq!(Celsius::from_raw(40), Celsius::new(40).unwrap());
+        assert_eq!(Celsius::new(200).unwrap().raw_value(), 200);
+        assert_eq!(Celsius::from_raw(200).degrees(), 200);
+        // deliberately rejected since the default encoding became unsigned;
+        // it used to encode as 0xff
+        assert!(Celsius::new(-1).is_err());
+        assert!(Celsius::new(256).is_err());
+    }
+
+    #[test]
+    fn dumps() {
+        use crate::protocol::{MemoryMapped, OcsdSensor, OcsdVersion};
+        use TemperatureEncoding::*;
+
+        // sensors in the layout of a dump from bus 3: a hot GPU reading 200°C
+        // (0xc8) with thresholds of 210 and 190°C, and one reporting the 0xff
+        // sentinel
+        const HOT: [u8; 32] = [
+            0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xd2, 0x00, 0x00, 0x00, 0xbe, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x0b, 0x00, 0xc8, 0x00, 0x00, 0x00, 0x84, 0xdb, 0x00, 0x00,
+            0x1f, 0x22, 0xf4, 0xff,
+        ];
+        const SENTINEL: [u8; 32] = [
+            0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x67, 0x00, 0x00, 0x00, 0x5d, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x0b, 0x00, 0xff, 0x00, 0x00, 0x00, 0x84, 0xdb, 0x00, 0x00,
+            0xb0, 0x22, 0xf4, 0xff,
+        ];
+        let hot = OcsdSensor::from_bytes_checked(&HOT, 0x03).unwrap();
+        let sentinel = OcsdSensor::from_bytes_checked(&SENTINEL, 0x03).unwrap();
+        assert_eq!(
+            (hot.reading.raw_value(), sentinel.reading.raw_value()),
+            (0xc8, 0xff)
+        );
+
+        // reading, caution and max continuous threshold of the hot GPU, and
+        // the sentinel
+        for (encoding, expected) in [
+            (Unsigned, [200, 210, 190, 255]),
+            (Signed, [-56, -46, -66, -1]),
+            (Offset(40), [160, 170, 150, 215]),
+        ] {
+            let decoded = [
+                hot.reading,
+                hot.caution_threshold,
+                hot.max_continuous_threshold,
+                sentinel.reading,
+            ]
+            .map(|temperature| encoding.decode(temperature));
+            assert_eq!(decoded, expected, "{}", encoding);
+        }
+
+        // version 2 buffers are read as unsigned, matching the hot GPU
+        let encoding = TemperatureEncoding::for_version(OcsdVersion::Version2);
+        assert_eq!(encoding, Unsigned);
+        assert_eq!(hot.reading.degrees(), 200);
+        assert_eq!(encoding.decode(sentinel.reading), 255);
+        assert_eq!(sentinel.to_bytes(), SENTINEL);
+    }
+
+    #[test]
+    fn encodings() {
+        use TemperatureEncoding::*;
+
+        for (encoding, min, max) in [
+            (Unsigned, 0, 255),
+            (Signed, -128, 127),
+            (Offset(40), -40, 215),
+        ] {
+            assert_eq!((encoding.min(), encoding.max()), (min, max));
+            for degrees in [min, 0, 40, max] {
+                let temperature = encoding.encode(degrees).unwrap();
+                assert_eq!(encoding.decode(temperature), degrees, "{}", encoding);
+            }
+            assert!(encoding.encode(min - 1).is_err());
+            assert!(encoding.encode(max + 1).is_err());
+            assert_eq!(
+                encoding.encode_saturating(i16::MIN).raw_value(),
+                encoding.encode(min).unwrap().raw_value()
+            );
+            assert_eq!(
+                encoding.encode_saturating(i16::MAX).raw_value(),
+                encoding.encode(max).unwrap().raw_value()
+            );
+            assert_eq!(
+                encoding.to_string().parse::<TemperatureEncoding>(),
+                Ok(encoding)
+            );
+        }
+
+        // every raw value decodes, and encodes back to itself
+        for raw in 0..=u8::MAX {
+            for encoding in [Unsigned, Signed, Offset(40)] {
+                let degrees = encoding.decode(Celsius::from_raw(raw));
+                assert_eq!(encoding.encode(degrees).unwrap().raw_value(), raw);
+            }
+        }
+
+        assert_eq!(Signed.encode(-1).unwrap().raw_value(), 0xff);
+        assert_eq!(Offset(40).encode(-40).unwrap().raw_value(), 0x00);
+        assert_eq!(
+            Unsigned
+                .encode_millidegrees(199_500, Rounding::Nearest)
+                .unwrap()
+                .raw_value(),
+            200
+        );
+        assert_eq!(
+            TemperatureEncoding::for_version(OcsdVersion::Version2),
+            Unsigned
+        );
+        assert!("offset".parse::<TemperatureEncoding>().is_err());
+        assert!("offset:256".parse::<TemperatureEncoding>().is_err());
+    }
+
+    #[test]
+    fn rounding() {
+        let cases = [
+            // millidegrees, nearest, floor, ceil
+            (0, 0, 0, 0),
+            (39_499, 39, 39, 40),
+            (39_500, 40, 39, 40),
+            (40_000, 40, 40, 40),
+            (-1_499, -1, -2, -1),
+            (-1_500, -2, -2, -1),
+            (-1_501, -2, -2, -1),
+        ];
+        for (millidegrees, nearest, floor, ceil) in cases {
+            for (rounding, degrees) in [
+                (Rounding::Nearest, nearest),
+                (Rounding::Floor, floor),
+                (Rounding::Ceil, ceil),
+            ] {
+                let encoding = TemperatureEncoding::Signed;
+                let celsius = encoding
+                    .encode_millidegrees(millidegrees, rounding)
+                    .unwrap();
+                assert_eq!(
+                    encoding.decode(celsius),
+                    degrees,
+                    "{} {:?}",
+                    millidegrees,
+                    rounding
+                );
+                if millidegrees >= 0 {
+                    let celsius =
+                        Celsius::from_f64(millidegrees as f64 / 1000.0, rounding).unwrap();
+                    assert_eq!(
+                        celsius.degrees(),
+                        degrees,
+                        "{} {:?}",
+                        millidegrees,
+                        rounding
+                    );
+                }
+            }
+        }
+        // float noise doesn't push the result up a degree
+        assert_eq!(
+            Celsius::from_f64(313.15 - 273.15, Rounding::Ceil)
+                .unwrap()
+                .degrees(),
+            40
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            Celsius::from_kelvin(273, Rounding::Nearest)
+                .unwrap()
+                .degrees(),
+            0
+        );
+        assert_eq!(
+            Celsius::from_kelvin(273, Rounding::Ceil).unwrap().degrees(),
+            0
+        );
+        // -0.15°C
+        assert!(Celsius::from_kelvin(273, Rounding::Floor).is_err());
+        assert!(Celsius::from_kelvin(0, Rounding::Nearest).is_err());
+
+        assert!(Celsius::from_millidegrees(255_499, Rounding::Nearest).is_ok());
+        assert!(Celsius::from_millidegrees(255_500, Rounding::Nearest).is_err());
+        assert!(Celsius::from_millidegrees(-501, Rounding::Nearest).is_err());
+        assert!(Celsius::from_millidegrees(i64::MIN, Rounding::Ceil).is_err());
+        assert!(Celsius::from_f64(f64::INFINITY, Rounding::Nearest).is_err());
+        assert!(Celsius::from_f64(f64::NAN, Rounding::Nearest).is_err());
+    }
+
+    #[test]
+    fn saturating() {
+        assert_eq!(Celsius::new_saturating(300), Celsius::MAX);
+        assert_eq!(Celsius::new_saturating(-200), Celsius::MIN);
+        assert_eq!(Celsius::MAX.degrees(), 255);
+        assert_eq!(Celsius::new_saturating(40).degrees(), 40);
+        assert_eq!(
+            Celsius::from_millidegrees_saturating(i64::MAX, Rounding::Nearest),
+            Celsius::MAX
+        );
+        assert_eq!(
+            Celsius::from_kelvin_saturating(0, Rounding::Nearest),
+            Celsius::MIN
+        );
+        assert_eq!(
+            Celsius::from_kelvin_saturating(u16::MAX, Rounding::Nearest),
+            Celsius::MAX
+        );
+        assert_eq!(
+            Celsius::from_f64_saturating(f64::NEG_INFINITY, Rounding::Nearest),
+            Celsius::MIN
+        );
+        assert_eq!(
+            Celsius::from_f64_saturating(f64::NAN, Rounding::Nearest),
+            Celsius::MAX
+        );
+        assert_eq!(
+            Celsius::from_f64_saturating(39.5, Rounding::Floor).degrees(),
+            39
+        );
     }
 }
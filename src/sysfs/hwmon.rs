@@ -0,0 +1,239 @@
+//! Temperature readings from hwmon drivers in `/sys/class/hwmon`.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::protocol::{Celsius, Rounding, TemperatureEncoding};
+
+/// A hwmon chip, as found in `/sys/class/hwmon/hwmon*`.
+#[derive(Debug, Clone)]
+pub struct HwmonChip {
+    /// Path to the chip's directory
+    pub path: PathBuf,
+    /// Name of the driver providing the chip, e.g. `amdgpu` or `nvme`
+    pub name: String,
+}
+
+/// A single `temp*_input` of a hwmon chip.
+#[derive(Debug, Clone)]
+pub struct TempInput {
+    /// Path to the `temp*_input` file
+    pub path: PathBuf,
+    /// Index of the input, e.g. 1 for `temp1_input`
+    pub index: u32,
+    /// Contents of the matching `temp*_label` file, if present
+    pub label: Option<String>,
+}
+
+/// Lists all hwmon chips below the provided sysfs root, ordered by path.
+pub fn chips(sysfs_root: impl AsRef<Path>) -> io::Result<Vec<HwmonChip>> {
+    let mut chips = Vec::new();
+    for entry in fs::read_dir(sysfs_root.as_ref().join("class/hwmon"))? {
+        let path = entry?.path();
+        let name = fs::read_to_string(path.join("name"))
+            .map(|name| name.trim().to_string())
+            .unwrap_or_default();
+        chips.push(HwmonChip { path, name });
+    }
+    chips.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(chips)
+}
+
+impl HwmonChip {
+    /// Lists the chip's temperature inputs, ordered by index.
+    pub fn temp_inputs(&self) -> io::Result<Vec<TempInput>> {
+        let mut inputs = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            let index = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("temp")?.strip_suffix("_input"))
+                .and_then(|index| index.parse().ok());
+            if let Some(index) = index {
+                let label = fs::read_to_string(self.path.join(format!("temp{}_label", index)))
+                    .ok()
+                    .map(|label| label.trim().to_string());
+                inputs.push(TempInput { path, index, label });
+            }
+        }
+        inputs.sort_by_key(|input| input.index);
+        Ok(inputs)
+    }
+}
+
+impl TempInput {
+    /// Reads the current temperature, rounded to the nearest degree.
+    pub fn read(&self) -> io::Result<Celsius> {
+        read_temperature(&self.path)
+    }
+}
+
+/// Reads a temperature in millidegrees Celsius from a hwmon `temp*_input`
+/// file, rounded to the nearest degree.
+pub fn read_temperature(path: impl AsRef<Path>) -> io::Result<Celsius> {
+    read_temperature_with(path, TemperatureEncoding::default())
+}
+
+/// Reads a temperature in millidegrees Celsius from a hwmon `temp*_input`
+/// file, rounded to the nearest degree and encoded with the provided
+/// encoding.
+pub fn read_temperature_with(
+    path: impl AsRef<Path>,
+    encoding: TemperatureEncoding,
+) -> io::Result<Celsius> {
+    let millidegrees: i64 = fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    encoding
+        .encode_millidegrees(millidegrees, Rounding::Nearest)
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("temperature {} m°C is out of range", millidegrees),
+            )
+        })
+}
+
+/// Selects a temperature input of a hwmon chip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputSelector {
+    /// Input with the provided index, e.g. 1 for `temp1_input`
+    Index(u32),
+    /// Input with the provided label, e.g. `edge` or `Composite`
+    Label(String),
+}
+
+/// Selects a temperature input by chip name, which unlike the `hwmon*`
+/// numbering is stable across reboots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HwmonSelector {
+    /// Name of the chip
+    pub chip: String,
+    /// Which of the chip's inputs to use
+    pub input: InputSelector,
+}
+
+impl HwmonSelector {
+    /// Finds the matching temperature input below the provided sysfs root.
+    /// If multiple chips have the same name, the first with a matching
+    /// input is used.
+    pub fn resolve(&self, sysfs_root: impl AsRef<Path>) -> io::Result<TempInput> {
+        for chip in chips(sysfs_root)?
+            .into_iter()
+            .filter(|chip| chip.name == self.chip)
+        {
+            let found = chip
+                .temp_inputs()?
+                .into_iter()
+                .find(|input| match &self.input {
+                    InputSelector::Index(index) => input.index == *index,
+                    InputSelector::Label(label) => input.label.as_ref() == Some(label),
+                });
+            if let Some(input) = found {
+                return Ok(input);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no hwmon temperature input matching {:?}", self),
+        ))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Creates a hwmon chip with the provided temperature inputs, given as
+    /// (index, label, millidegrees).
+    pub(crate) fn add_chip(
+        sysfs_root: &Path,
+        hwmon: &str,
+        name: &str,
+        inputs: &[(u32, Option<&str>, i64)],
+    ) -> PathBuf {
+        let path = sysfs_root.join("class/hwmon").join(hwmon);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("name"), format!("{}\n", name)).unwrap();
+        for (index, label, millidegrees) in inputs {
+            fs::write(
+                path.join(format!("temp{}_input", index)),
+                format!("{}\n", millidegrees),
+            )
+            .unwrap();
+            if let Some(label) = label {
+                fs::write(path.join(format!("temp{}_label", index)), label).unwrap();
+            }
+        }
+        path
+    }
+
+    #[test]
+    fn enumerate() {
+        let root = tempfile::tempdir().unwrap();
+        add_chip(
+            root.path(),
+            "hwmon1",
+            "nvme",
+            &[(1, Some("Composite"), 38850)],
+        );
+        add_chip(
+            root.path(),
+            "hwmon0",
+            "amdgpu",
+            &[(2, Some("junction"), 51000), (1, Some("edge"), 45000)],
+        );
+
+        let chips = chips(root.path()).unwrap();
+        assert_eq!(chips.len(), 2);
+        assert_eq!(chips[0].name, "amdgpu");
+        let inputs = chips[0].temp_inputs().unwrap();
+        assert_eq!(inputs[0].index, 1);
+        assert_eq!(inputs[0].label.as_deref(), Some("edge"));
+        assert_eq!(inputs[1].read().unwrap().degrees(), 51);
+    }
+
+    #[test]
+    fn select() {
+        let root = tempfile::tempdir().unwrap();
+        add_chip(
+            root.path(),
+            "hwmon0",
+            "nvme",
+            &[(1, Some("Composite"), 38500)],
+        );
+        add_chip(
+            root.path(),
+            "hwmon1",
+            "nvme",
+            &[(1, Some("Composite"), -1500)],
+        );
+        add_chip(root.path(), "hwmon2", "amdgpu", &[(3, Some("mem"), 60000)]);
+
+        let selector = HwmonSelector {
+            chip: "nvme".to_string(),
+            input: InputSelector::Label("Composite".to_string()),
+        };
+        let input = selector.resolve(root.path()).unwrap();
+        assert!(input
+            .path
+            .starts_with(root.path().join("class/hwmon/hwmon0")));
+        assert_eq!(input.read().unwrap().degrees(), 39);
+
+        let selector = HwmonSelector {
+            chip: "amdgpu".to_string(),
+            input: InputSelector::Index(1),
+        };
+        assert!(selector.resolve(root.path()).is_err());
+
+        let path = root.path().join("class/hwmon/hwmon1/temp1_input");
+        assert!(read_temperature(&path).is_err());
+        let encoding = TemperatureEncoding::Signed;
+        let reading = read_temperature_with(&path, encoding).unwrap();
+        assert_eq!(encoding.decode(reading), -2);
+    }
+}
@@ -0,0 +1,10 @@
+//! Access to host information exposed by the Linux kernel in sysfs.
+//!
+//! All functions take the sysfs root (`/sys` on a live system) so that they can
+//! be used against a copy or a simulated tree.
+
+pub mod hwmon;
+pub mod pci;
+
+/// Default location of sysfs.
+pub const SYSFS_ROOT: &str = "/sys";
@@ -0,0 +1,249 @@
+//! Resolution of PCI addresses for OCSD device headers.
+//!
+//! OCSD identifies an option card by its PCI bus and device number. These can be
+//! taken from a PCI address (`0000:04:00.0`), or from any sysfs path belonging to
+//! the device, such as `/sys/bus/pci/devices/0000:04:00.0` or a hwmon node
+//! (`/sys/class/hwmon/hwmon3`, or its `device` link).
+
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::protocol::{DeviceVersion, OcsdDeviceFlags, OcsdDeviceHeader};
+
+/// Address of a PCI function, in the form `domain:bus:device.function`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PciAddress {
+    /// PCI domain (segment); OCSD can only describe devices in domain 0
+    pub domain: u16,
+    /// PCI bus
+    pub bus: u8,
+    /// Device number on the bus, less than 32
+    pub device: u8,
+    /// Function number of the device, less than 8
+    pub function: u8,
+}
+
+impl Display for PciAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{}",
+            self.domain, self.bus, self.device, self.function
+        )
+    }
+}
+
+/// Parses an address of the form `0000:04:00.0`; the domain may be omitted,
+/// as in `04:00.0`.
+impl FromStr for PciAddress {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid PCI address: {}", s),
+            )
+        };
+        let (rest, function) = s.rsplit_once('.').ok_or_else(invalid)?;
+        let mut parts = rest.rsplit(':');
+        let device = parts.next().ok_or_else(invalid)?;
+        let bus = parts.next().ok_or_else(invalid)?;
+        let domain = parts.next().unwrap_or("0");
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let address = Self {
+            domain: u16::from_str_radix(domain, 16).map_err(|_| invalid())?,
+            bus: u8::from_str_radix(bus, 16).map_err(|_| invalid())?,
+            device: u8::from_str_radix(device, 16).map_err(|_| invalid())?,
+            function: function.parse().map_err(|_| invalid())?,
+        };
+        if address.device >= 32 || address.function >= 8 {
+            return Err(invalid());
+        }
+        Ok(address)
+    }
+}
+
+impl PciAddress {
+    /// Finds the PCI device owning a sysfs path, which may be a device
+    /// directory, a symlink to one (such as a hwmon node's `device` link), or
+    /// any path below it.
+    pub fn from_sysfs_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = fs::canonicalize(path)?;
+        path.ancestors()
+            .filter_map(|ancestor| ancestor.file_name()?.to_str()?.parse().ok())
+            .next()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} does not belong to a PCI device", path.display()),
+                )
+            })
+    }
+
+    /// Resolves a PCI address or sysfs path to the address of a device which
+    /// exists below the provided sysfs root.
+    pub fn find(sysfs_root: impl AsRef<Path>, address_or_path: &str) -> io::Result<Self> {
+        let address = match address_or_path.parse() {
+            Ok(address) => address,
+            Err(_) if address_or_path.contains('/') => Self::from_sysfs_path(address_or_path)?,
+            Err(e) => return Err(e),
+        };
+        address.sysfs_path(sysfs_root)?;
+        Ok(address)
+    }
+
+    /// Returns the device's directory below the provided sysfs root,
+    /// or an error if the device does not exist.
+    pub fn sysfs_path(&self, sysfs_root: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let path = sysfs_root
+            .as_ref()
+            .join("bus/pci/devices")
+            .join(self.to_string());
+        if path.exists() {
+            Ok(path)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no such PCI device: {}", self),
+            ))
+        }
+    }
+
+    /// Returns the PCI bus to set as [OcsdSensor::bus](crate::protocol::OcsdSensor::bus)
+    /// of the device's sensors.
+    ///
+    /// Returns an error if the device is outside of PCI domain 0.
+    pub fn sensor_bus(&self) -> io::Result<u8> {
+        self.check_domain()?;
+        Ok(self.bus)
+    }
+
+    /// Builds the OCSD device header for this device, with the provided
+    /// flags/caps. The function number is not represented in OCSD.
+    ///
+    /// Returns an error if the device is outside of PCI domain 0.
+    pub fn device_header(&self, flags_caps: OcsdDeviceFlags) -> io::Result<OcsdDeviceHeader> {
+        self.check_domain()?;
+        Ok(OcsdDeviceHeader {
+            version: DeviceVersion::Version1,
+            pci_bus: self.bus,
+            pci_device: self.device,
+            flags_caps,
+            ..Default::default()
+        })
+    }
+
+    fn check_domain(&self) -> io::Result<()> {
+        if self.domain == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("PCI device {} is outside of domain 0", self),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    const GPU: PciAddress = PciAddress {
+        domain: 0,
+        bus: 0x04,
+        device: 0,
+        function: 0,
+    };
+
+    /// Creates a sysfs tree with a GPU at 0000:04:00.0, which has a hwmon node.
+    fn sysfs() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let device = root
+            .path()
+            .join("devices/pci0000:00/0000:00:03.0/0000:04:00.0");
+        fs::create_dir_all(device.join("hwmon/hwmon3")).unwrap();
+        symlink("../..", device.join("hwmon/hwmon3/device")).unwrap();
+        fs::create_dir_all(root.path().join("bus/pci/devices")).unwrap();
+        symlink(
+            "../../../devices/pci0000:00/0000:00:03.0/0000:04:00.0",
+            root.path().join("bus/pci/devices/0000:04:00.0"),
+        )
+        .unwrap();
+        fs::create_dir_all(root.path().join("class/hwmon")).unwrap();
+        symlink(
+            "../../devices/pci0000:00/0000:00:03.0/0000:04:00.0/hwmon/hwmon3",
+            root.path().join("class/hwmon/hwmon3"),
+        )
+        .unwrap();
+        root
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("0000:04:00.0".parse::<PciAddress>().unwrap(), GPU);
+        assert_eq!("04:00.0".parse::<PciAddress>().unwrap(), GPU);
+        assert_eq!(
+            "10001:c1:1f.7".parse::<PciAddress>().ok(),
+            None,
+            "domain must fit in 16 bits"
+        );
+        let address: PciAddress = "abcd:c1:1f.7".parse().unwrap();
+        assert_eq!(address.to_string(), "abcd:c1:1f.7");
+        assert!("0000:04:20.0".parse::<PciAddress>().is_err());
+        assert!("0000:04:00.8".parse::<PciAddress>().is_err());
+        assert!("0000:04:00".parse::<PciAddress>().is_err());
+        assert!("hwmon3".parse::<PciAddress>().is_err());
+    }
+
+    #[test]
+    fn find() {
+        let root = sysfs();
+        let sysfs = root.path();
+        let find = |spec: &str| PciAddress::find(sysfs, spec);
+
+        assert_eq!(find("0000:04:00.0").unwrap(), GPU);
+        assert_eq!(find("04:00.0").unwrap(), GPU);
+        for path in [
+            "bus/pci/devices/0000:04:00.0",
+            "class/hwmon/hwmon3",
+            "class/hwmon/hwmon3/device",
+        ] {
+            assert_eq!(find(sysfs.join(path).to_str().unwrap()).unwrap(), GPU);
+        }
+
+        assert_eq!(
+            find("0000:05:00.0").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            find(sysfs.join("class").to_str().unwrap())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn device_header() {
+        let header = GPU.device_header(OcsdDeviceFlags::HOST_REPORTED).unwrap();
+        assert_eq!((header.pci_bus, header.pci_device), (0x04, 0x00));
+        assert_eq!(GPU.sensor_bus().unwrap(), 0x04);
+
+        let other_domain = PciAddress { domain: 1, ..GPU };
+        assert!(other_domain
+            .device_header(OcsdDeviceFlags::HOST_REPORTED)
+            .is_err());
+        assert!(other_domain.sensor_bus().is_err());
+    }
+}